@@ -1,14 +1,125 @@
-use crate::split::StatusReport;
+use crate::split::presets::{Preset, CATALOG, CATALOG_VERSION};
+use crate::split::{
+    chapters, detect_headers, encoding, numbering_warnings, page_lines, text_entries, Boilerplate,
+    Book, Boundary, CancelToken, Candidate, Format, JoinSummary, Level, ManifestFormat, Markdown,
+    Matter, MatterOutput, Metadata, Naming, Numbering, Output, PageLine, PageLineKind, SizeLimit,
+    SizeUnit, SplitBy, SplitConfig, SplitError, StatusReport,
+};
 use crossbeam_channel::{unbounded, Receiver};
-use native_dialog::FileDialog;
+use encoding_rs::Encoding;
 use std::path::PathBuf;
+use std::time::Duration;
+
+// Native builds read and write files directly and split on a thread pool.
+#[cfg(not(target_arch = "wasm32"))]
+use crate::split::{
+    join_folder, output_folders, read_book, split_batch, split_chapters, Job, JoinConfig,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use native_dialog::FileDialog;
+#[cfg(not(target_arch = "wasm32"))]
 use tokio::runtime;
 
+// The browser build splits in memory, with books uploaded and chapters downloaded as a ZIP.
+#[cfg(target_arch = "wasm32")]
+use crate::split::{decode_book, split_book, ZipSink};
+#[cfg(target_arch = "wasm32")]
+use crate::web;
+
 enum ParsingStatus {
     NotStarted,
     Working,
     Done,
-    Error(anyhow::Error),
+    /// Holds the names of files the cancelled split left behind.
+    Cancelled(Vec<String>),
+    Error(SplitError),
+}
+
+/// A book waiting in the batch queue.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+struct QueuedBook {
+    path: PathBuf,
+    /// Header regex of this book, empty to use the one of the settings.
+    pattern: String,
+    /// Text file to split if the book is a ZIP archive holding several.
+    #[serde(default)]
+    entry: String,
+}
+
+/// A book of the running or last batch.
+#[cfg(not(target_arch = "wasm32"))]
+struct BatchJob {
+    book: PathBuf,
+    /// `NotStarted` while the book waits for its turn.
+    status: ParsingStatus,
+    headers: usize,
+    /// How the book was split, which tells whether it should have had headers.
+    split_by: SplitBy,
+}
+
+/// A header match or a part of a chapter cut by size shown in the preview.
+struct PreviewHit {
+    /// Chapter number with the part letter, if any.
+    label: String,
+    /// One-based line number of the header in the book.
+    line: usize,
+    /// Number of headers of outer levels above it.
+    depth: usize,
+    /// The header is of an outer level.
+    division: bool,
+    title: String,
+    lines: usize,
+    chars: usize,
+}
+
+/// Split settings the preview depends on.
+#[derive(PartialEq)]
+struct PreviewQuery {
+    split_by: SplitBy,
+    pattern: String,
+    levels: Vec<Level>,
+    numbering: Numbering,
+    toc_depth: usize,
+    start_chapter: usize,
+    size_limit: Option<SizeLimit>,
+    gutenberg: Boilerplate,
+    remove_page_lines: bool,
+    front_matter: MatterOutput,
+    end_marker: String,
+    back_matter: MatterOutput,
+}
+
+/// Header matches of the current settings, computed without writing anything.
+struct Preview {
+    /// Book path, archive entry and encoding `book` was loaded with.
+    source: Option<(PathBuf, String, Option<String>)>,
+    /// Book path `entries` were listed for.
+    entries_of: Option<PathBuf>,
+    /// Text files of the book if it is a ZIP archive.
+    entries: Vec<String>,
+    book: Result<Book, String>,
+    /// Split settings `hits` were computed for.
+    query: Option<PreviewQuery>,
+    hits: Result<Vec<PreviewHit>, String>,
+    /// Chapter numbers in `hits` that skip, repeat or go backwards.
+    warnings: Vec<String>,
+    /// Page numbers and running heads left out of `hits`, with their text.
+    removed: Vec<(PageLine, String)>,
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self {
+            source: None,
+            entries_of: None,
+            entries: Vec::new(),
+            book: Ok(Book::from_text("")),
+            query: None,
+            hits: Ok(Vec::new()),
+            warnings: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
 }
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
@@ -17,9 +128,71 @@ enum ParsingStatus {
 pub struct TemplateApp {
     // Example stuff:
     book_path: PathBuf,
+    /// Text file to split if the book is a ZIP archive holding several.
+    book_entry: String,
     result_folder: PathBuf,
     header_req: String,
+    /// Header regexes saved by the user.
+    presets: Vec<Preset>,
+    /// Newest built-in preset catalog version the user has looked at.
+    presets_seen: u32,
+    #[serde(skip)]
+    preset_name: String,
+    /// Header levels above chapters, outermost first.
+    levels: Vec<Level>,
+    numbering: Numbering,
+    split_by: SplitBy,
+    size_limit: SizeLimit,
+    /// Cut chapters bigger than `size_limit` when splitting by headers.
+    split_long: bool,
+    gutenberg: Boilerplate,
+    remove_page_lines: bool,
+    front_matter: MatterOutput,
+    end_marker: String,
+    back_matter: MatterOutput,
+    toc_depth: usize,
     start_chapter: usize,
+    /// Name of the encoding chosen by the user, `None` to detect it.
+    encoding: Option<String>,
+    keep_encoding: bool,
+    reflow: bool,
+    manifest: Option<ManifestFormat>,
+    markdown: Markdown,
+    /// Pack the files of a split into a ZIP archive named after the book in `result_folder`.
+    zip_output: bool,
+    /// Folder inside the archive the files go to, empty for its top.
+    zip_root: String,
+    format: Format,
+    name_template: String,
+    nested_folders: bool,
+    metadata: Metadata,
+    live_preview: bool,
+    /// File chapters of the result folder are joined into.
+    joined_book: PathBuf,
+    /// Text put between joined chapters.
+    join_separator: String,
+    #[serde(skip)]
+    join_result: Option<Result<JoinSummary, SplitError>>,
+    /// Books to split in one go, each into a folder under `result_folder`.
+    queue: Vec<QueuedBook>,
+    /// Number of books of the queue split at once.
+    batch_concurrency: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    batch: Vec<BatchJob>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    batch_cancel: CancelToken,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    batch_channel: Option<Receiver<(usize, StatusReport)>>,
+    #[serde(skip)]
+    preview: Preview,
+    /// Header regexes suggested by "Auto-detect", or why there are none.
+    #[serde(skip)]
+    candidates: Option<Result<Vec<Candidate>, String>>,
+    #[serde(skip)]
+    detected_encoding: Option<&'static Encoding>,
     #[serde(skip)]
     lines_processed: usize,
     #[serde(skip)]
@@ -29,31 +202,223 @@ pub struct TemplateApp {
     #[serde(skip)]
     last_hit: Option<String>,
     #[serde(skip)]
+    warnings: Vec<String>,
+    #[serde(skip)]
+    cancel: CancelToken,
+    /// Output of the last started split, kept to clean up after cancelling.
+    #[serde(skip)]
+    last_output: Option<Output>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
     runtime: runtime::Runtime,
     #[serde(skip)]
     channel: Option<Receiver<StatusReport>>,
+    /// The book uploaded to the browser, in place of `book_path`.
+    #[cfg(target_arch = "wasm32")]
+    #[serde(skip)]
+    upload: Option<web::Upload>,
+    /// Brings in the book being uploaded.
+    #[cfg(target_arch = "wasm32")]
+    #[serde(skip)]
+    uploads: Option<Receiver<web::Upload>>,
 }
 
 impl Default for TemplateApp {
     fn default() -> Self {
         Self {
             book_path: Default::default(),
+            book_entry: String::new(),
             result_folder: Default::default(),
             header_req: Default::default(),
+            presets: Vec::new(),
+            presets_seen: CATALOG_VERSION,
+            preset_name: String::new(),
+            levels: Vec::new(),
+            numbering: Numbering::Continuous,
+            split_by: SplitBy::Pattern,
+            size_limit: SizeLimit::default(),
+            split_long: false,
+            gutenberg: Boilerplate::Separate,
+            remove_page_lines: false,
+            front_matter: MatterOutput::Chapter,
+            end_marker: String::new(),
+            back_matter: MatterOutput::Separate,
+            toc_depth: 1,
             start_chapter: 1,
+            encoding: None,
+            keep_encoding: false,
+            reflow: false,
+            manifest: None,
+            markdown: Markdown::default(),
+            zip_output: false,
+            zip_root: String::new(),
+            format: Format::Text,
+            name_template: Naming::default().template,
+            nested_folders: false,
+            metadata: Metadata::default(),
+            live_preview: false,
+            joined_book: PathBuf::new(),
+            join_separator: String::new(),
+            join_result: None,
+            queue: Vec::new(),
+            batch_concurrency: 2,
+            #[cfg(not(target_arch = "wasm32"))]
+            batch: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            batch_cancel: CancelToken::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            batch_channel: None,
+            preview: Preview::default(),
+            candidates: None,
+            detected_encoding: None,
             lines_processed: 0,
             chapters_saved: 0,
             status: ParsingStatus::NotStarted,
             last_hit: None,
+            warnings: Vec::new(),
+            cancel: CancelToken::default(),
+            last_output: None,
+            #[cfg(not(target_arch = "wasm32"))]
             runtime: runtime::Builder::new_multi_thread()
                 .enable_all()
                 .build()
                 .unwrap(),
             channel: None,
+            #[cfg(target_arch = "wasm32")]
+            upload: None,
+            #[cfg(target_arch = "wasm32")]
+            uploads: None,
         }
     }
 }
 
+fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::Text => "Text files",
+        Format::Epub => "EPUB",
+        Format::Fb2 => "FB2",
+        Format::Fb2Chapters => "FB2 per chapter",
+        Format::Markdown => "Markdown",
+    }
+}
+
+fn show_size_limit(ui: &mut egui::Ui, limit: &mut SizeLimit) {
+    let unit_name = |unit| match unit {
+        SizeUnit::Words => "words",
+        SizeUnit::Chars => "characters",
+        SizeUnit::Bytes => "bytes",
+    };
+    let boundary_name = |boundary| match boundary {
+        Boundary::Paragraph => "at paragraphs",
+        Boundary::Sentence => "at sentences",
+    };
+    ui.horizontal(|ui| {
+        ui.label("Part size: ");
+        ui.add(
+            egui::DragValue::new(&mut limit.target)
+                .clamp_range(1..=usize::MAX)
+                .speed(10.0),
+        );
+        egui::ComboBox::from_id_source("size unit")
+            .selected_text(unit_name(limit.unit))
+            .show_ui(ui, |ui| {
+                for unit in [SizeUnit::Words, SizeUnit::Chars, SizeUnit::Bytes] {
+                    ui.selectable_value(&mut limit.unit, unit, unit_name(unit));
+                }
+            });
+        egui::ComboBox::from_id_source("size boundary")
+            .selected_text(boundary_name(limit.boundary))
+            .show_ui(ui, |ui| {
+                for boundary in [Boundary::Paragraph, Boundary::Sentence] {
+                    ui.selectable_value(&mut limit.boundary, boundary, boundary_name(boundary));
+                }
+            });
+        ui.label("± ");
+        ui.add(
+            egui::DragValue::new(&mut limit.tolerance)
+                .clamp_range(0..=50)
+                .suffix("%"),
+        );
+    });
+}
+
+fn matter_combo(ui: &mut egui::Ui, id_source: &str, matter: &mut MatterOutput) {
+    let name = |matter| match matter {
+        MatterOutput::Chapter => "Write as a chapter",
+        MatterOutput::Separate => "Write to its own file",
+        MatterOutput::Discard => "Leave out",
+    };
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(name(*matter))
+        .show_ui(ui, |ui| {
+            for option in [
+                MatterOutput::Chapter,
+                MatterOutput::Separate,
+                MatterOutput::Discard,
+            ] {
+                ui.selectable_value(matter, option, name(option));
+            }
+        });
+}
+
+fn numbering_combo(ui: &mut egui::Ui, id_source: impl std::hash::Hash, numbering: &mut Numbering) {
+    let name = |numbering| match numbering {
+        Numbering::Continuous => "Continuous",
+        Numbering::Restart => "Restart per level above",
+    };
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(name(*numbering))
+        .show_ui(ui, |ui| {
+            for option in [Numbering::Continuous, Numbering::Restart] {
+                ui.selectable_value(numbering, option, name(option));
+            }
+        });
+}
+
+fn show_warnings(ui: &mut egui::Ui, id_source: &str, warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        format!("Numbering warnings: {}", warnings.len()),
+    );
+    egui::ScrollArea::vertical()
+        .id_source(id_source)
+        .max_height(100.0)
+        .show(ui, |ui| {
+            for warning in warnings {
+                ui.colored_label(ui.visuals().warn_fg_color, warning);
+            }
+        });
+}
+
+/// List the page numbers and running heads the split leaves out, for checking that no text
+/// is among them.
+fn show_removed_lines(ui: &mut egui::Ui, removed: &[(PageLine, String)]) {
+    egui::CollapsingHeader::new(format!("Page lines removed: {}", removed.len()))
+        .id_source("removed lines")
+        .show(ui, |ui| {
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .id_source("removed lines scroll")
+                .max_height(150.0)
+                .auto_shrink([false, true])
+                .show_rows(ui, row_height, removed.len(), |ui, range| {
+                    for (page_line, text) in &removed[range] {
+                        let kind = match page_line.kind {
+                            PageLineKind::PageNumber => "page number",
+                            PageLineKind::RunningHead => "running head",
+                        };
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("line {:>6} {kind:<12} ", page_line.line + 1));
+                            ui.label(text);
+                        });
+                    }
+                });
+        });
+}
+
 fn load_fonts(ctx: &egui::Context) {
     let mut fonts = egui::FontDefinitions::default();
     fonts.font_data.insert(
@@ -94,6 +459,907 @@ impl TemplateApp {
         Default::default()
     }
 
+    fn selected_entry(&self) -> Option<&str> {
+        Some(self.book_entry.as_str()).filter(|entry| !entry.is_empty())
+    }
+
+    fn selected_encoding(&self) -> Option<&'static Encoding> {
+        self.encoding
+            .as_ref()
+            .and_then(|name| Encoding::for_label(name.as_bytes()))
+    }
+
+    fn split_config(&self) -> SplitConfig {
+        SplitConfig {
+            cancel: self.cancel.clone(),
+            split_by: self.split_by,
+            levels: self.levels.clone(),
+            numbering: self.numbering,
+            toc_depth: self.toc_depth,
+            start_chapter: self.start_chapter,
+            size_limit: (self.split_by == SplitBy::Size || self.split_long)
+                .then(|| self.size_limit.clone()),
+            gutenberg: self.gutenberg,
+            remove_page_lines: self.remove_page_lines,
+            front_matter: self.front_matter,
+            end_marker: self.end_marker.clone(),
+            back_matter: self.back_matter,
+            encoding: self.selected_encoding(),
+            entry: self.selected_entry().map(str::to_owned),
+            keep_encoding: self.keep_encoding,
+            reflow: self.reflow,
+            manifest: self.manifest,
+            format: self.format,
+            markdown: self.markdown.clone(),
+            naming: Naming {
+                template: self.name_template.clone(),
+                nested: self.nested_folders,
+            },
+            metadata: self.metadata.clone(),
+            output: if self.zip_output {
+                let stem = self.book_path.file_stem().unwrap_or_default();
+                Output::Zip {
+                    file: self
+                        .result_folder
+                        .join(format!("{}.zip", stem.to_string_lossy())),
+                    root: self.zip_root.clone(),
+                }
+            } else {
+                Output::Folder(self.result_folder.clone())
+            },
+            ..SplitConfig::new(self.header_req.clone())
+        }
+    }
+
+    fn detect_headers(&mut self) {
+        self.load_book();
+        self.candidates = Some(match &self.preview.book {
+            Ok(book) => Ok(detect_headers(&book.text)),
+            Err(e) => Err(e.clone()),
+        });
+    }
+
+    /// Suggested header regexes, each with a button to use it.
+    fn show_candidates(&mut self, ui: &mut egui::Ui) {
+        let Some(candidates) = &self.candidates else {
+            return;
+        };
+        let mut chosen = None;
+        let mut close = false;
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("Suggested header regexes");
+                close = ui.small_button("✖").clicked();
+            });
+            match candidates {
+                Err(e) => {
+                    ui.label(format!("Error: {e}"));
+                }
+                Ok(candidates) if candidates.is_empty() => {
+                    ui.label("Nothing looks like chapter headers");
+                }
+                Ok(candidates) => {
+                    for candidate in candidates {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            if ui.button("Use").clicked() {
+                                chosen = Some(candidate.pattern.clone());
+                            }
+                            ui.label(format!(
+                                "{}: {} matches",
+                                candidate.description, candidate.count
+                            ));
+                        });
+                        ui.monospace(&candidate.pattern);
+                        for sample in &candidate.samples {
+                            ui.weak(sample);
+                        }
+                    }
+                }
+            }
+        });
+        if let Some(pattern) = chosen {
+            self.header_req = pattern;
+            close = true;
+        }
+        if close {
+            self.candidates = None;
+        }
+    }
+
+    /// Built-in and saved header regexes to pick from, and saving the current one.
+    fn show_presets(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Preset: ");
+            let selected = CATALOG
+                .iter()
+                .map(|preset| (preset.name, preset.pattern))
+                .chain(
+                    self.presets
+                        .iter()
+                        .map(|p| (p.name.as_str(), p.pattern.as_str())),
+                )
+                .find(|(_, pattern)| *pattern == self.header_req)
+                .map_or("Custom", |(name, _)| name)
+                .to_owned();
+            let mut remove = None;
+            let opened = egui::ComboBox::from_id_source("preset")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for preset in CATALOG {
+                        let mut label = preset.name.to_owned();
+                        if preset.since > self.presets_seen {
+                            label.push_str(" (new)");
+                        }
+                        let selected = self.header_req == preset.pattern;
+                        if ui
+                            .selectable_label(selected, label)
+                            .on_hover_text(format!("Matches lines like “{}”", preset.sample))
+                            .clicked()
+                        {
+                            self.header_req = preset.pattern.to_owned();
+                        }
+                    }
+                    if !self.presets.is_empty() {
+                        ui.separator();
+                    }
+                    for (i, preset) in self.presets.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let selected = self.header_req == preset.pattern;
+                            if ui.selectable_label(selected, &preset.name).clicked() {
+                                self.header_req = preset.pattern.clone();
+                            }
+                            if ui
+                                .small_button("✖")
+                                .on_hover_text("Delete preset")
+                                .clicked()
+                            {
+                                remove = Some(i);
+                            }
+                        });
+                    }
+                })
+                .inner
+                .is_some();
+            if opened {
+                self.presets_seen = CATALOG_VERSION;
+            }
+            if let Some(i) = remove {
+                self.presets.remove(i);
+            }
+
+            ui.text_edit_singleline(&mut self.preset_name)
+                .on_hover_text("Name to save the header regex under");
+            let can_save = !self.preset_name.trim().is_empty() && !self.header_req.is_empty();
+            if ui
+                .add_enabled(can_save, egui::Button::new("Save preset"))
+                .clicked()
+            {
+                let preset = Preset {
+                    name: self.preset_name.trim().to_owned(),
+                    pattern: self.header_req.clone(),
+                };
+                match self.presets.iter_mut().find(|p| p.name == preset.name) {
+                    Some(existing) => *existing = preset,
+                    None => self.presets.push(preset),
+                }
+                self.preset_name.clear();
+            }
+        });
+    }
+
+    /// Markdown output, offered by the header regex since headers become its headings.
+    fn show_markdown(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut markdown = self.format == Format::Markdown;
+            if ui
+                .checkbox(&mut markdown, "Markdown with headings")
+                .on_hover_text("Write chapters as Markdown files with the header as a heading")
+                .changed()
+            {
+                self.format = if markdown {
+                    Format::Markdown
+                } else {
+                    Format::Text
+                };
+            }
+            if markdown {
+                ui.checkbox(&mut self.markdown.escape, "Escape formatting")
+                    .on_hover_text("Keep characters such as * and _ from being read as Markdown");
+                ui.checkbox(&mut self.markdown.front_matter, "YAML front matter")
+                    .on_hover_text(
+                        "Chapter number and title, book, word count and links to the files \
+                         before and after",
+                    );
+            }
+        });
+    }
+
+    /// Editors for the header levels above chapters, outermost first.
+    fn show_levels(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("Level {} regex: ", i + 1));
+                ui.text_edit_singleline(&mut level.pattern);
+                numbering_combo(ui, ("level numbering", i), &mut level.numbering);
+                if ui.small_button("✖").on_hover_text("Remove level").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.levels.remove(i);
+        }
+        if ui
+            .button("Add level")
+            .on_hover_text("Add a level of headers, such as parts, above chapters")
+            .clicked()
+        {
+            self.levels.push(Level::default());
+        }
+    }
+
+    fn show_output_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Output format: ");
+            egui::ComboBox::from_id_source("format")
+                .selected_text(format_name(self.format))
+                .show_ui(ui, |ui| {
+                    for format in [
+                        Format::Text,
+                        Format::Markdown,
+                        Format::Epub,
+                        Format::Fb2,
+                        Format::Fb2Chapters,
+                    ] {
+                        ui.selectable_value(&mut self.format, format, format_name(format));
+                    }
+                });
+        });
+
+        if matches!(
+            self.format,
+            Format::Text | Format::Fb2Chapters | Format::Markdown
+        ) {
+            ui.horizontal(|ui| {
+                ui.label("File names: ");
+                ui.text_edit_singleline(&mut self.name_template)
+                    .on_hover_text(
+                        "{n} chapter number, {n:4} padded to 4 digits, {title} chapter title, \
+                         {slug} title as-words-like-this, {1} or {name} regex groups",
+                    );
+            });
+            ui.checkbox(&mut self.nested_folders, "Folder per header level")
+                .on_hover_text("Put chapters into a folder for every part they lie under");
+        }
+        ui.checkbox(&mut self.reflow, "Join wrapped lines into paragraphs")
+            .on_hover_text(
+                "For books wrapped at a fixed width; poetry, lists and indented blocks stay \
+                 as they are",
+            );
+        ui.horizontal(|ui| {
+            ui.label("Manifest: ");
+            let name = |manifest| match manifest {
+                None => "None",
+                Some(ManifestFormat::Json) => "JSON",
+                Some(ManifestFormat::Csv) => "CSV",
+            };
+            egui::ComboBox::from_id_source("manifest")
+                .selected_text(name(self.manifest))
+                .show_ui(ui, |ui| {
+                    for option in [None, Some(ManifestFormat::Json), Some(ManifestFormat::Csv)] {
+                        ui.selectable_value(&mut self.manifest, option, name(option));
+                    }
+                });
+        })
+        .response
+        .on_hover_text("A file listing every chapter with its file, lines and place in the book");
+        // The browser always downloads an archive.
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.checkbox(&mut self.zip_output, "Pack into a ZIP archive")
+            .on_hover_text("Write one archive named after the book into the result folder");
+        if self.zip_output || cfg!(target_arch = "wasm32") {
+            ui.horizontal(|ui| {
+                ui.label("Folder in archive: ");
+                ui.add(egui::TextEdit::singleline(&mut self.zip_root).hint_text("None"));
+            });
+        }
+
+        match self.format {
+            Format::Text => {
+                ui.checkbox(&mut self.keep_encoding, "Write chapters in book encoding");
+            }
+            Format::Markdown => {}
+            Format::Epub | Format::Fb2 | Format::Fb2Chapters => {
+                // Empty fields are filled from the book, so show what it has.
+                let found = match &self.preview.book {
+                    Ok(book) => book.metadata.clone(),
+                    Err(_) => Metadata::default(),
+                };
+                let language = if found.language.is_empty() {
+                    "en".to_owned()
+                } else {
+                    found.language
+                };
+                for (label, value, hint) in [
+                    ("Title: ", &mut self.metadata.title, found.title),
+                    ("Author: ", &mut self.metadata.author, found.author),
+                    ("Language: ", &mut self.metadata.language, language),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        ui.add(egui::TextEdit::singleline(value).hint_text(hint));
+                    });
+                }
+            }
+        }
+    }
+
+    /// Joining the chapters in the result folder back into a book.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_join(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Book file: ");
+            let mut path = self.joined_book.display().to_string();
+            if ui.text_edit_singleline(&mut path).changed() {
+                self.joined_book = PathBuf::from(path);
+            }
+            if ui.button("Browse").clicked() {
+                let path = FileDialog::new()
+                    .add_filter("Text", &["txt"])
+                    .show_save_single_file()
+                    .unwrap();
+                if let Some(path) = path {
+                    self.joined_book = path;
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Between chapters: ");
+            ui.add(
+                egui::TextEdit::multiline(&mut self.join_separator)
+                    .desired_rows(1)
+                    .hint_text("Nothing"),
+            );
+        });
+        if ui
+            .button("Join")
+            .on_hover_text(
+                "Put the text chapters of the result folder back together, in the order of \
+                 its manifest or else by name",
+            )
+            .clicked()
+        {
+            let config = JoinConfig {
+                folder: self.result_folder.clone(),
+                output: self.joined_book.clone(),
+                separator: self.join_separator.clone(),
+                encoding: None,
+            };
+            self.join_result = Some(join_folder(&config));
+        }
+        match &self.join_result {
+            None => {}
+            Some(Ok(summary)) => {
+                ui.label(format!("Joined {} files", summary.files.len()));
+                match summary.matches_source {
+                    Some(true) => ui.label("The book is identical to the one split"),
+                    Some(false) => ui.label("The book differs from the one split"),
+                    None => ui.label("No manifest.json to check the book against"),
+                };
+                for warning in &summary.warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, warning);
+                }
+            }
+            Some(Err(e)) => {
+                ui.label(format!("Error: {e}"));
+            }
+        }
+    }
+
+    /// The batch queue, with the status of every book once started.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_batch(&mut self, ui: &mut egui::Ui) {
+        let running = self.batch_channel.is_some();
+        ui.horizontal(|ui| {
+            if ui.button("Add books").clicked() {
+                let paths = FileDialog::new()
+                    .add_filter("Books", &["txt", "epub", "fb2", "zip", "gz", "bz2"])
+                    .show_open_multiple_file()
+                    .unwrap();
+                self.queue.extend(paths.into_iter().map(|path| QueuedBook {
+                    path,
+                    pattern: String::new(),
+                    entry: String::new(),
+                }));
+            }
+            if ui.button("Add this book").clicked() && !self.book_path.as_os_str().is_empty() {
+                self.queue.push(QueuedBook {
+                    path: self.book_path.clone(),
+                    pattern: self.header_req.clone(),
+                    entry: self.book_entry.clone(),
+                });
+            }
+            if ui.button("Clear").clicked() && !running {
+                self.queue.clear();
+            }
+        });
+
+        let mut remove = None;
+        egui::Grid::new("queue").striped(true).show(ui, |ui| {
+            for (i, book) in self.queue.iter_mut().enumerate() {
+                let name = book.path.file_name().unwrap_or_default();
+                ui.label(name.to_string_lossy())
+                    .on_hover_text(book.path.display().to_string());
+                ui.add(
+                    egui::TextEdit::singleline(&mut book.pattern)
+                        .hint_text("Header regex of the settings"),
+                );
+                match self.batch.get(i).filter(|job| job.book == book.path) {
+                    Some(job) => {
+                        match &job.status {
+                            ParsingStatus::NotStarted => ui.label("Waiting"),
+                            ParsingStatus::Working => ui.spinner(),
+                            ParsingStatus::Done
+                                if job.headers == 0 && job.split_by != SplitBy::Size =>
+                            {
+                                ui.colored_label(ui.visuals().warn_fg_color, "No headers matched")
+                            }
+                            ParsingStatus::Done => ui.label(format!("{} headers", job.headers)),
+                            ParsingStatus::Cancelled(_) => ui.label("Cancelled"),
+                            ParsingStatus::Error(e) => ui
+                                .colored_label(ui.visuals().error_fg_color, "Failed")
+                                .on_hover_text(e.to_string()),
+                        };
+                    }
+                    None => {
+                        ui.label("");
+                    }
+                }
+                if ui
+                    .add_enabled(!running, egui::Button::new("Remove"))
+                    .clicked()
+                {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.queue.remove(i);
+            self.batch.clear();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Books at once: ");
+            ui.add(egui::DragValue::new(&mut self.batch_concurrency).clamp_range(1..=16));
+        });
+        ui.label("Every book goes to a folder named after it in the result folder.");
+        if running {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                if ui.button("Cancel").clicked() {
+                    self.batch_cancel.cancel();
+                }
+            });
+        } else if ui
+            .add_enabled(!self.queue.is_empty(), egui::Button::new("Start batch"))
+            .clicked()
+        {
+            self.start_batch();
+        }
+
+        let finished = !running && !self.batch.is_empty();
+        if finished {
+            let failed: Vec<&BatchJob> = self
+                .batch
+                .iter()
+                .filter(|job| matches!(job.status, ParsingStatus::Error(_)))
+                .collect();
+            ui.label(format!(
+                "Split {} of {} books",
+                self.batch.len() - failed.len(),
+                self.batch.len()
+            ));
+            for job in failed {
+                if let ParsingStatus::Error(e) = &job.status {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        format!("{}: {e}", job.book.display()),
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_batch(&mut self) {
+        self.batch_cancel = CancelToken::default();
+        let shared = SplitConfig {
+            cancel: self.batch_cancel.clone(),
+            // Title and author are those of one book.
+            metadata: Metadata {
+                language: self.metadata.language.clone(),
+                ..Metadata::default()
+            },
+            ..self.split_config()
+        };
+        let paths: Vec<PathBuf> = self.queue.iter().map(|book| book.path.clone()).collect();
+        let folders = output_folders(&self.result_folder, &paths);
+        let jobs: Vec<Job> = self
+            .queue
+            .iter()
+            .zip(folders)
+            .map(|(book, folder)| {
+                let shared = SplitConfig {
+                    entry: Some(book.entry.clone()).filter(|entry| !entry.is_empty()),
+                    ..shared.clone()
+                };
+                let config = if book.pattern.is_empty() {
+                    shared
+                } else {
+                    SplitConfig {
+                        split_by: SplitBy::Pattern,
+                        pattern: book.pattern.clone(),
+                        ..shared
+                    }
+                };
+                Job {
+                    book: book.path.clone(),
+                    config: SplitConfig {
+                        output: config.output.for_book(&folder),
+                        ..config
+                    },
+                }
+            })
+            .collect();
+        self.batch = jobs
+            .iter()
+            .map(|job| BatchJob {
+                book: job.book.clone(),
+                status: ParsingStatus::NotStarted,
+                headers: 0,
+                split_by: job.config.split_by,
+            })
+            .collect();
+        let (tx, rx) = unbounded();
+        self.batch_channel = Some(rx);
+        self.runtime
+            .spawn(split_batch(jobs, self.batch_concurrency, tx));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn parse_batch_channel(&mut self) {
+        let Some(rx) = &self.batch_channel else {
+            return;
+        };
+        while let Ok((i, report)) = rx.try_recv() {
+            let job = &mut self.batch[i];
+            match report {
+                StatusReport::Started => job.status = ParsingStatus::Working,
+                StatusReport::NewTitle(_) => job.headers += 1,
+                StatusReport::Error(e) => job.status = ParsingStatus::Error(e),
+                StatusReport::Done => job.status = ParsingStatus::Done,
+                StatusReport::Cancelled(files) => job.status = ParsingStatus::Cancelled(files),
+                _ => {}
+            }
+        }
+        let finished = self.batch.iter().all(|job| {
+            matches!(
+                job.status,
+                ParsingStatus::Done | ParsingStatus::Cancelled(_) | ParsingStatus::Error(_)
+            )
+        });
+        if finished {
+            self.batch_channel = None;
+        }
+    }
+
+    /// List the text and FB2 files of the book again if its path changed since they were last
+    /// listed, which only reads the directory of a ZIP archive. An archive of several books
+    /// starts with the first one picked.
+    fn load_entries(&mut self) {
+        if self.preview.entries_of.as_ref() != Some(&self.book_path) {
+            self.preview.entries = self.text_entries().unwrap_or_default();
+            if !self.preview.entries.contains(&self.book_entry) {
+                self.book_entry = self.preview.entries.first().cloned().unwrap_or_default();
+            }
+            self.preview.entries_of = Some(self.book_path.clone());
+        }
+    }
+
+    /// Read the book again if its path, archive entry or encoding changed since it was last
+    /// read.
+    fn load_book(&mut self) {
+        self.load_entries();
+        let source = (
+            self.book_path.clone(),
+            self.book_entry.clone(),
+            self.encoding.clone(),
+        );
+        if self.preview.source.as_ref() != Some(&source) {
+            self.preview.book = self.read_book().map_err(|e| e.to_string());
+            self.preview.source = Some((
+                self.book_path.clone(),
+                self.book_entry.clone(),
+                self.encoding.clone(),
+            ));
+            self.preview.query = None;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_book(&self) -> Result<Book, SplitError> {
+        read_book(
+            &self.book_path,
+            self.selected_encoding(),
+            self.selected_entry(),
+        )
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read_book(&self) -> Result<Book, SplitError> {
+        match &self.upload {
+            Some((name, bytes)) => decode_book(
+                bytes,
+                name.as_ref(),
+                self.selected_encoding(),
+                self.selected_entry(),
+            ),
+            None => Err(SplitError::Malformed("no book opened".to_owned())),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn text_entries(&self) -> Result<Vec<String>, SplitError> {
+        let file = std::fs::File::open(&self.book_path)?;
+        text_entries(std::io::BufReader::new(file))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn text_entries(&self) -> Result<Vec<String>, SplitError> {
+        match &self.upload {
+            Some((_, bytes)) => text_entries(std::io::Cursor::new(bytes)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// A choice of the text or FB2 file to split when the book is an archive holding several.
+    fn show_entries(&mut self, ui: &mut egui::Ui) {
+        // Lists the files of an archive as soon as it is picked.
+        self.load_entries();
+        if self.preview.entries.len() < 2 {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Book file: ");
+            egui::ComboBox::from_id_source("entry")
+                .selected_text(&self.book_entry)
+                .show_ui(ui, |ui| {
+                    for entry in &self.preview.entries {
+                        ui.selectable_value(&mut self.book_entry, entry.clone(), entry);
+                    }
+                });
+        })
+        .response
+        .on_hover_text("The archive holds several books; this one is split");
+    }
+
+    /// The book path, or in the browser the uploaded book, and where chapters go.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_files(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Book path: ");
+            let mut path = self.book_path.display().to_string();
+            ui.text_edit_singleline(&mut path);
+            if ui.button("Browse").clicked() {
+                let path = FileDialog::new()
+                    .set_location("~/")
+                    .add_filter("Books", &["txt", "epub", "fb2", "zip", "gz", "bz2"])
+                    .show_open_single_file()
+                    .unwrap();
+
+                self.book_path = path.unwrap_or_default();
+            }
+        });
+        self.show_entries(ui);
+
+        ui.horizontal(|ui| {
+            ui.label("Result folder: ");
+            let mut path = self.result_folder.display().to_string();
+            ui.text_edit_singleline(&mut path);
+            if ui.button("Browse").clicked() {
+                let path = FileDialog::new().show_open_single_dir().unwrap();
+
+                self.result_folder = path.unwrap_or_default();
+            }
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn show_files(&mut self, ui: &mut egui::Ui) {
+        if let Some(upload) = self.uploads.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.book_path = PathBuf::from(&upload.0);
+            self.upload = Some(upload);
+            self.uploads = None;
+            // The book may have the name of the last one.
+            self.preview.source = None;
+            self.preview.entries_of = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Book: ");
+            match &self.upload {
+                Some((name, _)) => ui.label(name),
+                None => ui.label("none"),
+            };
+            if ui.button("Open").clicked() {
+                let (tx, rx) = unbounded();
+                web::pick_file(".txt,.epub,.fb2,.zip,.gz,.bz2", tx, ui.ctx().clone());
+                self.uploads = Some(rx);
+            }
+        });
+        self.show_entries(ui);
+        ui.label("Chapters are downloaded as a ZIP archive.");
+    }
+
+    /// Start splitting the book with the current settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start(&mut self) {
+        let (tx, rx) = unbounded();
+        self.cancel = CancelToken::default();
+        let config = self.split_config();
+        self.last_output = Some(config.output.clone());
+        let file = self.book_path.clone();
+        self.channel = Some(rx);
+        self.runtime.spawn(split_chapters(config, file, tx));
+    }
+
+    /// Split the uploaded book in memory and offer the chapters for download as a ZIP archive.
+    /// There are no threads to split on in the browser, so this returns once the split is done.
+    #[cfg(target_arch = "wasm32")]
+    fn start(&mut self) {
+        let (tx, rx) = unbounded();
+        self.cancel = CancelToken::default();
+        let config = self.split_config();
+        tx.send(StatusReport::Started).unwrap();
+        let split = self.read_book().and_then(|book| {
+            tx.send(StatusReport::EncodingDetected(book.encoding))
+                .unwrap();
+            let mut zip = std::io::Cursor::new(Vec::new());
+            let mut sink = ZipSink::new(&mut zip, &self.zip_root);
+            split_book(&config, &book, &mut sink, &mut |report| {
+                tx.send(report).unwrap()
+            })?;
+            drop(sink);
+            Ok(zip.into_inner())
+        });
+        let stem = self.book_path.file_stem().unwrap_or_default();
+        let report = match split.and_then(|zip| {
+            let name = format!("{}.zip", stem.to_string_lossy());
+            web::download(&name, &zip, "application/zip")?;
+            Ok(())
+        }) {
+            Ok(()) => StatusReport::Done,
+            Err(e) => StatusReport::Error(e),
+        };
+        tx.send(report).unwrap();
+        self.channel = Some(rx);
+    }
+
+    /// Re-run header matching if the book or the pattern changed since the last frame.
+    fn refresh_preview(&mut self) {
+        self.load_book();
+
+        let config = self.split_config();
+        let query = PreviewQuery {
+            split_by: config.split_by,
+            pattern: config.pattern.clone(),
+            levels: config.levels.clone(),
+            numbering: config.numbering,
+            toc_depth: config.toc_depth,
+            start_chapter: config.start_chapter,
+            size_limit: config.size_limit.clone(),
+            gutenberg: config.gutenberg,
+            remove_page_lines: config.remove_page_lines,
+            front_matter: config.front_matter,
+            end_marker: config.end_marker.clone(),
+            back_matter: config.back_matter,
+        };
+        if self.preview.query.as_ref() == Some(&query) {
+            return;
+        }
+        self.preview.warnings.clear();
+        self.preview.removed = match &self.preview.book {
+            Ok(book) if config.remove_page_lines => {
+                let lines: Vec<&str> = book.text.lines().collect();
+                page_lines(&config, book)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|page_line| (page_line, lines[page_line.line].trim().to_owned()))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        self.preview.hits = match &self.preview.book {
+            Err(e) => Err(e.clone()),
+            Ok(book) => chapters(&config, book)
+                .map(|chapters| {
+                    self.preview.warnings = numbering_warnings(&chapters);
+                    chapters
+                        .into_iter()
+                        .filter(|chapter| {
+                            chapter.header.is_some()
+                                || chapter.part.is_some()
+                                || chapter.matter != Matter::Body
+                                || config.split_by == SplitBy::Size
+                        })
+                        .map(|chapter| PreviewHit {
+                            label: chapter.number_label(4),
+                            line: chapter.first_line + 1,
+                            depth: chapter.parents.len(),
+                            division: chapter.division,
+                            title: chapter.title.unwrap_or_default().to_owned(),
+                            lines: chapter.lines.len(),
+                            chars: chapter.char_count(),
+                        })
+                        .collect()
+                })
+                .map_err(|e| e.to_string()),
+        };
+        self.preview.query = Some(query);
+    }
+
+    fn show_preview(&self, ui: &mut egui::Ui) {
+        ui.group(|ui| match &self.preview.hits {
+            Err(e) => {
+                ui.label(format!("Preview error: {e}"));
+            }
+            Ok(hits) => {
+                ui.label(match self.split_by {
+                    SplitBy::Size => format!("Parts: {}", hits.len()),
+                    _ => format!("Headers matched: {}", hits.len()),
+                });
+                let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, hits.len(), |ui, range| {
+                        for hit in &hits[range] {
+                            ui.horizontal(|ui| {
+                                ui.monospace(format!(
+                                    "{:<5} line {:>6} {:>6} lines {:>8} chars ",
+                                    hit.label, hit.line, hit.lines, hit.chars
+                                ));
+                                ui.add_space(hit.depth as f32 * 16.0);
+                                if hit.division {
+                                    ui.strong(&hit.title);
+                                } else {
+                                    ui.label(&hit.title);
+                                }
+                            });
+                        }
+                    });
+                show_warnings(ui, "preview warnings", &self.preview.warnings);
+                if self.remove_page_lines {
+                    show_removed_lines(ui, &self.preview.removed);
+                }
+            }
+        });
+    }
+
+    /// Remove the files left behind by a cancelled split.
+    fn delete_cancelled(&mut self) {
+        let (ParsingStatus::Cancelled(files), Some(output)) = (&self.status, &self.last_output)
+        else {
+            return;
+        };
+        self.status = match output.delete(files) {
+            Ok(()) => ParsingStatus::Cancelled(Vec::new()),
+            Err(e) => ParsingStatus::Error(e),
+        };
+    }
+
     fn parse_channel(&mut self) {
         let mut drop_channel = false;
         if let Some(rx) = &self.channel {
@@ -104,10 +1370,16 @@ impl TemplateApp {
                         self.lines_processed = 0;
                         self.chapters_saved = 0;
                         self.last_hit = None;
+                        self.warnings.clear();
+                        self.detected_encoding = None;
+                    }
+                    StatusReport::EncodingDetected(encoding) => {
+                        self.detected_encoding = Some(encoding)
                     }
                     StatusReport::LinesParsed(lines) => self.lines_processed = lines,
                     StatusReport::ChaptersSplit(chaps) => self.chapters_saved = chaps,
                     StatusReport::NewTitle(title) => self.last_hit = Some(title),
+                    StatusReport::Warning(warning) => self.warnings.push(warning),
                     StatusReport::Error(e) => {
                         self.status = ParsingStatus::Error(e);
                         drop_channel = true;
@@ -116,6 +1388,10 @@ impl TemplateApp {
                         self.status = ParsingStatus::Done;
                         drop_channel = true;
                     }
+                    StatusReport::Cancelled(files) => {
+                        self.status = ParsingStatus::Cancelled(files);
+                        drop_channel = true;
+                    }
                 }
             }
         }
@@ -131,6 +1407,15 @@ impl eframe::App for TemplateApp {
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.parse_channel();
+        let busy = self.channel.is_some();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.parse_batch_channel();
+        #[cfg(not(target_arch = "wasm32"))]
+        let busy = busy || self.batch_channel.is_some();
+        if busy {
+            // Progress comes from other threads, which cannot wake the UI.
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.with_layout(
@@ -139,73 +1424,154 @@ impl eframe::App for TemplateApp {
                     // The central panel the region left after adding TopPanel's and SidePanel's
                     ui.heading("Book Splitter");
 
+                    ui.group(|ui| self.show_files(ui));
+
                     ui.group(|ui| {
                         ui.horizontal(|ui| {
-                            ui.label("Book path: ");
-                            let mut path = self.book_path.display().to_string();
-                            ui.text_edit_singleline(&mut path);
-                            if ui.button("Browse").clicked() {
-                                let path = FileDialog::new()
-                                    .set_location("~/")
-                                    .add_filter("Text", &["txt"])
-                                    .show_open_single_file()
-                                    .unwrap();
-
-                                self.book_path = match path {
-                                    Some(path) => path,
-                                    None => PathBuf::default(),
-                                };
-                            }
+                            ui.label("Split by: ");
+                            ui.radio_value(&mut self.split_by, SplitBy::Pattern, "Header regex");
+                            ui.radio_value(&mut self.split_by, SplitBy::Toc, "Table of contents");
+                            ui.radio_value(&mut self.split_by, SplitBy::Size, "Size");
                         });
-
+                        match self.split_by {
+                            SplitBy::Pattern => {
+                                self.show_presets(ui);
+                                self.show_levels(ui);
+                                ui.horizontal(|ui| {
+                                    ui.label("Header regex: ");
+                                    ui.text_edit_singleline(&mut self.header_req).on_hover_text(
+                                        "Name groups (?P<number>...) and (?P<title>...) \
+                                             to take chapter numbers and titles from headers",
+                                    );
+                                    if ui
+                                        .button("Auto-detect")
+                                        .on_hover_text(
+                                            "Suggest regexes for the headers of the book",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.detect_headers();
+                                    }
+                                });
+                                self.show_candidates(ui);
+                            }
+                            SplitBy::Toc => {
+                                ui.horizontal(|ui| {
+                                    ui.label("Levels: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.toc_depth)
+                                            .clamp_range(1..=10)
+                                            .speed(0.1),
+                                    );
+                                });
+                            }
+                            SplitBy::Size => {}
+                        }
+                        self.show_markdown(ui);
+                        if self.split_by != SplitBy::Size {
+                            ui.checkbox(&mut self.split_long, "Cut long chapters");
+                        }
+                        if self.split_by == SplitBy::Size || self.split_long {
+                            show_size_limit(ui, &mut self.size_limit);
+                        }
                         ui.horizontal(|ui| {
-                            ui.label("Result folder: ");
-                            let mut path = self.result_folder.display().to_string();
-                            ui.text_edit_singleline(&mut path);
-                            if ui.button("Browse").clicked() {
-                                let path = FileDialog::new().show_open_single_dir().unwrap();
-
-                                self.result_folder = match path {
-                                    Some(path) => path,
-                                    None => PathBuf::default(),
-                                };
+                            ui.label("Start chapter: ");
+                            ui.add(egui::DragValue::new(&mut self.start_chapter).speed(0.1));
+                            let nested = match self.split_by {
+                                SplitBy::Pattern => !self.levels.is_empty(),
+                                SplitBy::Toc => self.toc_depth > 1,
+                                SplitBy::Size => false,
+                            };
+                            if nested {
+                                ui.label("Numbering: ");
+                                numbering_combo(ui, "numbering", &mut self.numbering);
                             }
                         });
-                    });
-
-                    ui.group(|ui| {
                         ui.horizontal(|ui| {
-                            ui.label("Header regex: ");
-                            ui.text_edit_singleline(&mut self.header_req);
+                            ui.label("Project Gutenberg header and license: ");
+                            let name = |boilerplate| match boilerplate {
+                                Boilerplate::Keep => "Keep in the text",
+                                Boilerplate::Separate => "Write to their own files",
+                                Boilerplate::Strip => "Leave out",
+                            };
+                            egui::ComboBox::from_id_source("gutenberg")
+                                .selected_text(name(self.gutenberg))
+                                .show_ui(ui, |ui| {
+                                    for option in [
+                                        Boilerplate::Keep,
+                                        Boilerplate::Separate,
+                                        Boilerplate::Strip,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut self.gutenberg,
+                                            option,
+                                            name(option),
+                                        );
+                                    }
+                                });
+                        });
+                        ui.checkbox(
+                            &mut self.remove_page_lines,
+                            "Remove page numbers and running heads",
+                        )
+                        .on_hover_text(
+                            "For text taken from scans or PDFs; check the removed lines \
+                             under the preview",
+                        );
+                        if self.split_by != SplitBy::Size {
+                            ui.horizontal(|ui| {
+                                ui.label("Text before the first header: ");
+                                matter_combo(ui, "front matter", &mut self.front_matter);
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("End marker: ");
+                            ui.text_edit_singleline(&mut self.end_marker).on_hover_text(
+                                "Regex matching the line back matter such as appendices or \
+                                 a license starts at",
+                            );
+                            if !self.end_marker.is_empty() {
+                                matter_combo(ui, "back matter", &mut self.back_matter);
+                            }
                         });
                         ui.horizontal(|ui| {
-                            ui.label("Start chapter: ");
-                            ui.add(egui::DragValue::new(&mut self.start_chapter).speed(0.1));
+                            ui.label("Encoding: ");
+                            egui::ComboBox::from_id_source("encoding")
+                                .selected_text(self.encoding.as_deref().unwrap_or("Auto-detect"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.encoding, None, "Auto-detect");
+                                    for encoding in encoding::SUPPORTED {
+                                        let name = encoding.name().to_owned();
+                                        ui.selectable_value(
+                                            &mut self.encoding,
+                                            Some(name),
+                                            encoding.name(),
+                                        );
+                                    }
+                                });
                         });
+                        ui.checkbox(&mut self.live_preview, "Live preview");
                     });
 
+                    ui.group(|ui| self.show_output_settings(ui));
+
+                    if self.live_preview {
+                        self.refresh_preview();
+                        self.show_preview(ui);
+                    }
+
                     match self.status {
                         ParsingStatus::Working => {
-                            ui.spinner();
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                if ui.button("Cancel").clicked() {
+                                    self.cancel.cancel();
+                                }
+                            });
                         }
                         _ => {
                             if ui.button("Start").clicked() {
-                                let (tx, rx) = unbounded();
-                                let pattern = self.header_req.clone();
-                                let file = self.book_path.clone();
-                                let folder = self.result_folder.clone();
-                                let start_chapter = self.start_chapter;
-                                self.channel = Some(rx);
-                                self.runtime.spawn(async move {
-                                    crate::split::split_chapters(
-                                        pattern,
-                                        file,
-                                        folder,
-                                        start_chapter,
-                                        tx,
-                                    )
-                                        .await
-                                });
+                                self.start();
                             }
                         }
                     }
@@ -215,6 +1581,13 @@ impl eframe::App for TemplateApp {
                         _ => {
                             ui.separator();
 
+                            if let Some(encoding) = self.detected_encoding {
+                                ui.horizontal(|ui| {
+                                    ui.label("Encoding: ");
+                                    ui.label(encoding.name());
+                                });
+                            }
+
                             ui.horizontal(|ui| {
                                 ui.label("Line: ");
                                 ui.label(self.lines_processed.to_string());
@@ -231,6 +1604,25 @@ impl eframe::App for TemplateApp {
                                     ui.label(title);
                                 });
                             }
+
+                            show_warnings(ui, "split warnings", &self.warnings);
+                        }
+                    }
+
+                    if let ParsingStatus::Cancelled(files) = &self.status {
+                        ui.separator();
+                        ui.label(format!("Cancelled, files written: {}", files.len()));
+                        if !files.is_empty() {
+                            egui::ScrollArea::vertical()
+                                .max_height(100.0)
+                                .show(ui, |ui| {
+                                    for file in files {
+                                        ui.monospace(file);
+                                    }
+                                });
+                            if ui.button("Delete written files").clicked() {
+                                self.delete_cancelled();
+                            }
                         }
                     }
 
@@ -239,6 +1631,17 @@ impl eframe::App for TemplateApp {
                         ui.label("Error: ");
                         ui.label(e.to_string());
                     }
+
+                    // Batches and joins work on folders, which the browser does not have.
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.separator();
+                        egui::CollapsingHeader::new(format!("Batch ({} books)", self.queue.len()))
+                            .id_source("batch")
+                            .show(ui, |ui| self.show_batch(ui));
+                        egui::CollapsingHeader::new("Join chapters into a book")
+                            .show(ui, |ui| self.show_join(ui));
+                    }
                 },
             );
         });
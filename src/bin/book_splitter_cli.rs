@@ -0,0 +1,673 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+// The command-line interface has no use in the browser.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> std::process::ExitCode {
+    cli::run()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cli {
+    use book_splitter::split::presets::{self, BuiltinPreset};
+    use book_splitter::split::{
+        chapters, detect_headers, encoding, join_folder, numbering_warnings, output_folders,
+        page_lines, read_book, split_batch, split_file, Boilerplate, Boundary, Format, Job,
+        JoinConfig, Level, ManifestFormat, Markdown, Matter, MatterOutput, Metadata, Naming,
+        Numbering, Output, PageLineKind, SizeLimit, SizeUnit, SplitBy, SplitConfig, SplitError,
+        StatusReport,
+    };
+    use clap::{Parser, ValueEnum};
+    use encoding_rs::Encoding;
+    use std::path::{Path, PathBuf};
+    use std::process::ExitCode;
+
+    const EXIT_USAGE: u8 = 2;
+    const EXIT_REGEX: u8 = 3;
+    const EXIT_IO: u8 = 4;
+    const EXIT_NO_HEADERS: u8 = 5;
+    const EXIT_FORMAT: u8 = 6;
+
+    /// Split a book into chapter files by a header regex or its table of contents, or join
+    /// them back.
+    #[derive(Parser)]
+    #[command(
+        version,
+        after_help = "Exit codes: 0 success, 2 bad arguments, 3 invalid regex, \
+                      4 I/O error, 5 no headers matched, 6 malformed book."
+    )]
+    struct Args {
+        /// Book to split, or with --join the folder of chapters to join. Given more than one
+        /// book, every book is split into a folder named after it under --output.
+        #[arg(required_unless_present = "queue")]
+        books: Vec<PathBuf>,
+        /// File listing books to split, one per line, each optionally followed by a tab and a
+        /// header regex of its own. Lines starting with `#` are skipped.
+        #[arg(long, value_name = "FILE")]
+        queue: Option<PathBuf>,
+        /// Number of books split at once when there are several.
+        #[arg(short, long, default_value_t = 4)]
+        jobs: usize,
+        /// Regex matching chapter header lines. Named groups `number` and `title` take the
+        /// chapter number (digits, Roman numerals or words) and title from the header.
+        #[arg(
+            short,
+            long,
+            required_unless_present_any = ["toc", "size", "detect", "preset", "join", "queue"]
+        )]
+        pattern: Option<String>,
+        /// Use a built-in header regex instead of --pattern.
+        #[arg(long, value_name = "NAME", value_parser = parse_preset, conflicts_with = "pattern")]
+        preset: Option<&'static BuiltinPreset>,
+        /// Regex matching headers of a level above chapters, such as parts. Repeat for more
+        /// levels, outermost first.
+        #[arg(short, long = "level", value_name = "REGEX", conflicts_with = "toc")]
+        levels: Vec<String>,
+        /// How headers of every level are counted, outermost first and chapters last; the last
+        /// value also counts the levels left out.
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "continuous")]
+        numbering: Vec<NumberingArg>,
+        /// Split at the table of contents entries of an EPUB or the sections of an FB2 instead
+        /// of a regex.
+        #[arg(long, conflicts_with_all = ["pattern", "preset"])]
+        toc: bool,
+        /// Number of table of contents levels to split at.
+        #[arg(long, default_value_t = 1)]
+        toc_depth: usize,
+        /// Cut chapters bigger than this into parts named like 0007a, 0007b. Without --pattern
+        /// or --toc the whole book is cut into numbered parts.
+        #[arg(long, value_name = "SIZE")]
+        size: Option<usize>,
+        /// What --size is measured in.
+        #[arg(long, value_enum, default_value_t = UnitArg::Words)]
+        unit: UnitArg,
+        /// Where parts may end.
+        #[arg(long, value_enum, default_value_t = BoundaryArg::Paragraph)]
+        boundary: BoundaryArg,
+        /// How far parts may stray from --size to end on a boundary, in percent.
+        #[arg(long, default_value_t = 10, value_name = "PERCENT")]
+        tolerance: usize,
+        /// Folder to write chapters to, or with --zip the archive, or with --join the file to
+        /// write the book to.
+        #[arg(short, long, required_unless_present_any = ["dry_run", "detect"])]
+        output: Option<PathBuf>,
+        /// Write chapters, the manifest and any EPUB or FB2 book into a ZIP archive instead of
+        /// a folder. Several books each get an archive named after it under --output.
+        #[arg(long, conflicts_with_all = ["join", "dry_run", "detect"])]
+        zip: bool,
+        /// Folder inside the archive to put the files in, such as `chapters`; they are at its
+        /// top if omitted.
+        #[arg(long, value_name = "DIR", default_value = "", requires = "zip")]
+        zip_root: String,
+        /// Number of the text before the first header; chapters follow it.
+        #[arg(short, long, default_value_t = 1)]
+        start: usize,
+        /// What to do with the Project Gutenberg header and license: keep them in the text,
+        /// write them to files of their own or strip them.
+        #[arg(long, value_enum, default_value_t = GutenbergArg::Separate)]
+        gutenberg: GutenbergArg,
+        /// Leave out page numbers and running heads of text taken from scans or PDFs.
+        #[arg(long)]
+        remove_page_lines: bool,
+        /// What to do with the text before the first header: write it as a chapter, to
+        /// 0000_front or leave it out.
+        #[arg(long, value_enum, default_value_t = MatterArg::Chapter)]
+        front: MatterArg,
+        /// Regex matching the line back matter such as appendices or a license starts at.
+        #[arg(long, value_name = "REGEX")]
+        end_marker: Option<String>,
+        /// What to do with the back matter: write it as a chapter, to 9999_back or leave it out.
+        #[arg(long, value_enum, default_value_t = MatterArg::Separate)]
+        back: MatterArg,
+        /// Encoding of the book, detected when omitted. With --join the encoding to write the
+        /// book in, by default that of the split book.
+        #[arg(short, long, value_parser = parse_encoding)]
+        encoding: Option<&'static Encoding>,
+        /// Text or FB2 file to split when the book is a ZIP archive holding several.
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+        /// Write chapters in the encoding of the book instead of UTF-8.
+        #[arg(long)]
+        keep_encoding: bool,
+        /// Join the lines of a hard-wrapped book into paragraphs, keeping poetry, lists and
+        /// indented blocks.
+        #[arg(long)]
+        reflow: bool,
+        /// File name template: {n}, {n:4}, {title}, {slug}, {1} or {name} for regex groups.
+        #[arg(short, long, default_value = "{n:4}")]
+        name: String,
+        /// Put chapters into a folder per header of an outer level they lie under.
+        #[arg(long)]
+        nested: bool,
+        /// What to write chapters as.
+        #[arg(short, long, value_enum, default_value_t = FormatArg::Text)]
+        format: FormatArg,
+        /// Write Markdown text as it is, without escaping characters such as `*` and `_`.
+        #[arg(long)]
+        no_escape: bool,
+        /// Leave out the YAML front matter of Markdown files.
+        #[arg(long)]
+        no_front_matter: bool,
+        /// Also write manifest.json or manifest.csv listing every chapter with its file, lines
+        /// and byte range in the book.
+        #[arg(long, value_enum)]
+        manifest: Option<ManifestArg>,
+        /// Book title for EPUB, FB2 and Markdown output, taken from a Project Gutenberg header
+        /// if omitted.
+        #[arg(long, default_value = "")]
+        title: String,
+        /// Book author for EPUB and FB2 output, taken from a Project Gutenberg header if
+        /// omitted.
+        #[arg(long, default_value = "")]
+        author: String,
+        /// Book language for EPUB and FB2 output, taken from a Project Gutenberg header or else
+        /// `en` if omitted.
+        #[arg(long, default_value = "")]
+        language: String,
+        /// List matched headers without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+        /// Suggest header regexes for the book, best first, with their match counts and a few
+        /// matched lines.
+        #[arg(long, conflicts_with_all = ["dry_run", "pattern", "toc"])]
+        detect: bool,
+        /// Join the chapter files of a split back into one book, in the order of its manifest
+        /// or else by name, and check it against the split book.
+        #[arg(long, conflicts_with_all = ["pattern", "preset", "toc", "size", "detect", "dry_run"])]
+        join: bool,
+        /// Text put between joined chapters; `\n` and `\t` stand for a line break and a tab.
+        #[arg(long, default_value = "", requires = "join")]
+        separator: String,
+        /// Only print errors.
+        #[arg(short, long)]
+        quiet: bool,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum FormatArg {
+        /// One text file per chapter.
+        Text,
+        /// A single EPUB book.
+        Epub,
+        /// A single FB2 book.
+        Fb2,
+        /// One FB2 book per chapter.
+        Fb2Chapters,
+        /// One Markdown file per chapter with the header as a heading and YAML front matter.
+        Markdown,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum NumberingArg {
+        /// Count on through the whole book.
+        Continuous,
+        /// Count from 1 again under every header of an outer level.
+        Restart,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum MatterArg {
+        Chapter,
+        Separate,
+        Discard,
+    }
+
+    impl From<MatterArg> for MatterOutput {
+        fn from(matter: MatterArg) -> Self {
+            match matter {
+                MatterArg::Chapter => MatterOutput::Chapter,
+                MatterArg::Separate => MatterOutput::Separate,
+                MatterArg::Discard => MatterOutput::Discard,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum GutenbergArg {
+        Keep,
+        Separate,
+        Strip,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum ManifestArg {
+        /// The source file, settings and chapters.
+        Json,
+        /// A table of the chapters.
+        Csv,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum UnitArg {
+        Words,
+        Chars,
+        /// Bytes of UTF-8 text.
+        Bytes,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum BoundaryArg {
+        Paragraph,
+        /// Paragraph or sentence.
+        Sentence,
+    }
+
+    impl From<NumberingArg> for Numbering {
+        fn from(numbering: NumberingArg) -> Self {
+            match numbering {
+                NumberingArg::Continuous => Numbering::Continuous,
+                NumberingArg::Restart => Numbering::Restart,
+            }
+        }
+    }
+
+    impl From<FormatArg> for Format {
+        fn from(format: FormatArg) -> Self {
+            match format {
+                FormatArg::Text => Format::Text,
+                FormatArg::Epub => Format::Epub,
+                FormatArg::Fb2 => Format::Fb2,
+                FormatArg::Fb2Chapters => Format::Fb2Chapters,
+                FormatArg::Markdown => Format::Markdown,
+            }
+        }
+    }
+
+    fn parse_encoding(name: &str) -> Result<&'static Encoding, String> {
+        Encoding::for_label(name.as_bytes()).ok_or_else(|| {
+            let known: Vec<_> = encoding::SUPPORTED.iter().map(|e| e.name()).collect();
+            format!("unknown encoding, try one of: {}", known.join(", "))
+        })
+    }
+
+    fn parse_preset(name: &str) -> Result<&'static BuiltinPreset, String> {
+        presets::builtin(name).ok_or_else(|| {
+            let known: Vec<_> = presets::CATALOG.iter().map(|p| p.name).collect();
+            format!("unknown preset, try one of: {}", known.join(", "))
+        })
+    }
+
+    fn error_code(e: &SplitError) -> u8 {
+        match e {
+            SplitError::Template(_) | SplitError::Join(_) => EXIT_USAGE,
+            SplitError::Regex(_) => EXIT_REGEX,
+            SplitError::Io(_) => EXIT_IO,
+            SplitError::Archive(_) | SplitError::Xml(_) | SplitError::Malformed(_) => EXIT_FORMAT,
+        }
+    }
+
+    pub fn run() -> ExitCode {
+        let args = Args::parse();
+        let batch = args.queue.is_some() || args.books.len() > 1;
+        let code = if batch && (args.detect || args.dry_run || args.join || args.entry.is_some()) {
+            eprintln!("Error: --detect, --dry-run, --join and --entry take a single book");
+            EXIT_USAGE
+        } else if batch {
+            batch_split(&args)
+        } else if args.detect {
+            detect(&args)
+        } else if args.join {
+            join(&args)
+        } else if args.dry_run {
+            dry_run(&args)
+        } else {
+            split(args)
+        };
+        ExitCode::from(code)
+    }
+
+    fn config(args: &Args) -> SplitConfig {
+        let numbering = |level: usize| {
+            args.numbering
+                .get(level)
+                .or(args.numbering.last())
+                .map_or(Numbering::Continuous, |&numbering| numbering.into())
+        };
+        SplitConfig {
+            split_by: if args.toc {
+                SplitBy::Toc
+            } else if args.pattern.is_none() && args.preset.is_none() && args.size.is_some() {
+                SplitBy::Size
+            } else {
+                SplitBy::Pattern
+            },
+            size_limit: args.size.map(|target| SizeLimit {
+                target,
+                unit: match args.unit {
+                    UnitArg::Words => SizeUnit::Words,
+                    UnitArg::Chars => SizeUnit::Chars,
+                    UnitArg::Bytes => SizeUnit::Bytes,
+                },
+                boundary: match args.boundary {
+                    BoundaryArg::Paragraph => Boundary::Paragraph,
+                    BoundaryArg::Sentence => Boundary::Sentence,
+                },
+                tolerance: args.tolerance,
+            }),
+            remove_page_lines: args.remove_page_lines,
+            gutenberg: match args.gutenberg {
+                GutenbergArg::Keep => Boilerplate::Keep,
+                GutenbergArg::Separate => Boilerplate::Separate,
+                GutenbergArg::Strip => Boilerplate::Strip,
+            },
+            front_matter: args.front.into(),
+            end_marker: args.end_marker.clone().unwrap_or_default(),
+            back_matter: args.back.into(),
+            levels: args
+                .levels
+                .iter()
+                .enumerate()
+                .map(|(level, pattern)| Level {
+                    pattern: pattern.clone(),
+                    numbering: numbering(level),
+                })
+                .collect(),
+            numbering: numbering(args.levels.len()),
+            toc_depth: args.toc_depth,
+            start_chapter: args.start,
+            naming: Naming {
+                template: args.name.clone(),
+                nested: args.nested,
+            },
+            encoding: args.encoding,
+            entry: args.entry.clone(),
+            keep_encoding: args.keep_encoding,
+            reflow: args.reflow,
+            manifest: args.manifest.map(|manifest| match manifest {
+                ManifestArg::Json => ManifestFormat::Json,
+                ManifestArg::Csv => ManifestFormat::Csv,
+            }),
+            format: args.format.into(),
+            markdown: Markdown {
+                escape: !args.no_escape,
+                front_matter: !args.no_front_matter,
+            },
+            metadata: Metadata {
+                title: args.title.clone(),
+                author: args.author.clone(),
+                language: args.language.clone(),
+            },
+            output: if args.zip {
+                Output::Zip {
+                    file: args.output.clone().unwrap_or_default(),
+                    root: args.zip_root.clone(),
+                }
+            } else {
+                Output::Folder(args.output.clone().unwrap_or_default())
+            },
+            ..SplitConfig::new(match args.preset {
+                Some(preset) => preset.pattern.to_owned(),
+                None => args.pattern.clone().unwrap_or_default(),
+            })
+        }
+    }
+
+    /// Print suggested header regexes.
+    fn detect(args: &Args) -> u8 {
+        let book = match read_book(&args.books[0], args.encoding, args.entry.as_deref()) {
+            Ok(book) => book,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return error_code(&e);
+            }
+        };
+        let candidates = detect_headers(&book.text);
+        if candidates.is_empty() {
+            eprintln!("No headers found");
+            return EXIT_NO_HEADERS;
+        }
+        for candidate in candidates {
+            println!(
+                "{}\t{}\t{}",
+                candidate.count, candidate.pattern, candidate.description
+            );
+            for sample in &candidate.samples {
+                println!("\t{sample}");
+            }
+        }
+        0
+    }
+
+    /// Print the headers the pattern matches, like the preview of the GUI.
+    fn dry_run(args: &Args) -> u8 {
+        let config = config(args);
+        let book = match read_book(&args.books[0], args.encoding, args.entry.as_deref()) {
+            Ok(book) => book,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return error_code(&e);
+            }
+        };
+        let chapters = match chapters(&config, &book) {
+            Ok(chapters) => chapters,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return error_code(&e);
+            }
+        };
+
+        for warning in numbering_warnings(&chapters) {
+            eprintln!("Warning: {warning}");
+        }
+        if config.remove_page_lines && !args.quiet {
+            let lines: Vec<&str> = book.text.lines().collect();
+            for page_line in page_lines(&config, &book).unwrap_or_default() {
+                let kind = match page_line.kind {
+                    PageLineKind::PageNumber => "page number",
+                    PageLineKind::RunningHead => "running head",
+                };
+                eprintln!(
+                    "Removed line {} ({kind}): {}",
+                    page_line.line + 1,
+                    lines[page_line.line].trim()
+                );
+            }
+        }
+
+        let by_size = config.split_by == SplitBy::Size;
+        let mut headers = 0;
+        for chapter in chapters {
+            if chapter.header.is_some() {
+                headers += 1;
+            } else if chapter.part.is_none() && chapter.matter == Matter::Body && !by_size {
+                continue;
+            }
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                chapter.number_label(4),
+                chapter.first_line + 1,
+                chapter.lines.len(),
+                chapter.char_count(),
+                "  ".repeat(chapter.parents.len()) + chapter.title.unwrap_or_default()
+            );
+        }
+
+        if headers == 0 && !by_size {
+            eprintln!("No headers matched");
+            EXIT_NO_HEADERS
+        } else {
+            0
+        }
+    }
+
+    /// Join chapter files back into a book.
+    fn join(args: &Args) -> u8 {
+        let config = JoinConfig {
+            folder: args.books[0].clone(),
+            output: args.output.clone().unwrap_or_default(),
+            separator: args.separator.replace("\\n", "\n").replace("\\t", "\t"),
+            encoding: args.encoding,
+        };
+        let summary = match join_folder(&config) {
+            Ok(summary) => summary,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return error_code(&e);
+            }
+        };
+        for warning in &summary.warnings {
+            eprintln!("Warning: {warning}");
+        }
+        if !args.quiet {
+            eprintln!(
+                "Done: {} files, {} bytes",
+                summary.files.len(),
+                summary.bytes
+            );
+            match summary.matches_source {
+                Some(true) => eprintln!("The book is identical to the one split"),
+                Some(false) => eprintln!("The book differs from the one split"),
+                None => {}
+            }
+        }
+        0
+    }
+
+    /// Books of the `queue` file with their own header regexes, if they have one.
+    fn read_queue(queue: &Path) -> std::io::Result<Vec<(PathBuf, Option<String>)>> {
+        let queue = std::fs::read_to_string(queue)?;
+        Ok(queue
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| match line.split_once('\t') {
+                Some((book, pattern)) => (PathBuf::from(book.trim()), Some(pattern.to_owned())),
+                None => (PathBuf::from(line.trim()), None),
+            })
+            .collect())
+    }
+
+    /// Split several books, each into a folder or archive of its own under the output folder.
+    fn batch_split(args: &Args) -> u8 {
+        let mut books: Vec<(PathBuf, Option<String>)> =
+            args.books.iter().map(|book| (book.clone(), None)).collect();
+        if let Some(queue) = &args.queue {
+            match read_queue(queue) {
+                Ok(queued) => books.extend(queued),
+                Err(e) => {
+                    eprintln!("Error: {}: {e}", queue.display());
+                    return EXIT_IO;
+                }
+            }
+        }
+        let shared = config(args);
+        if shared.split_by == SplitBy::Pattern
+            && shared.pattern.is_empty()
+            && books.iter().any(|(_, pattern)| pattern.is_none())
+        {
+            eprintln!(
+                "Error: books without a header regex of their own need --pattern, --preset, \
+                 --toc or --size"
+            );
+            return EXIT_USAGE;
+        }
+
+        let paths: Vec<PathBuf> = books.iter().map(|(book, _)| book.clone()).collect();
+        let folders = output_folders(args.output.as_deref().unwrap_or(Path::new(".")), &paths);
+        let jobs: Vec<Job> = books
+            .iter()
+            .zip(folders)
+            .map(|((book, pattern), folder)| Job {
+                book: book.clone(),
+                config: SplitConfig {
+                    split_by: match pattern {
+                        Some(_) => SplitBy::Pattern,
+                        None => shared.split_by,
+                    },
+                    pattern: pattern.clone().unwrap_or_else(|| shared.pattern.clone()),
+                    // Title and author are those of one book.
+                    metadata: Metadata {
+                        language: shared.metadata.language.clone(),
+                        ..Metadata::default()
+                    },
+                    output: shared.output.for_book(&folder),
+                    ..shared.clone()
+                },
+            })
+            .collect();
+        let by_size: Vec<bool> = jobs
+            .iter()
+            .map(|job| job.config.split_by == SplitBy::Size)
+            .collect();
+
+        let runtime = tokio::runtime::Runtime::new().expect("cannot start the tokio runtime");
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        runtime.spawn(split_batch(jobs, args.jobs, sender));
+        let mut headers = vec![0; books.len()];
+        let mut failures: Vec<(usize, String, u8)> = Vec::new();
+        for (i, report) in receiver {
+            let book = books[i].0.display();
+            let position = format!("[{}/{}]", i + 1, books.len());
+            match report {
+                StatusReport::Started if !args.quiet => eprintln!("{position} {book}"),
+                StatusReport::NewTitle(_) => headers[i] += 1,
+                StatusReport::Warning(warning) => eprintln!("Warning: {book}: {warning}"),
+                StatusReport::Done if headers[i] == 0 && !by_size[i] => failures.push((
+                    i,
+                    "no headers matched, the whole book was written as one chapter".to_owned(),
+                    EXIT_NO_HEADERS,
+                )),
+                StatusReport::Done if !args.quiet => {
+                    eprintln!("{position} Done: {book}, {} headers", headers[i])
+                }
+                StatusReport::Error(e) => failures.push((i, e.to_string(), error_code(&e))),
+                _ => {}
+            }
+        }
+
+        if !args.quiet || !failures.is_empty() {
+            eprintln!(
+                "Split {} of {} books",
+                books.len() - failures.len(),
+                books.len()
+            );
+        }
+        failures.sort_by_key(|&(i, _, _)| i);
+        for (i, reason, _) in &failures {
+            eprintln!("Failed: {}: {reason}", books[*i].0.display());
+        }
+        failures.first().map_or(0, |&(_, _, code)| code)
+    }
+
+    fn split(args: Args) -> u8 {
+        let config = config(&args);
+        let mut lines = 0;
+        let result = split_file(&config, &args.books[0], &mut |report| match report {
+            StatusReport::EncodingDetected(encoding) if !args.quiet => {
+                eprintln!("Encoding: {}", encoding.name());
+            }
+            StatusReport::LinesParsed(count) => lines = count,
+            StatusReport::NewTitle(title) if !args.quiet => {
+                eprintln!("Line {}: {title}", lines + 1);
+            }
+            StatusReport::Warning(warning) => eprintln!("Warning: {warning}"),
+            _ => {}
+        });
+
+        match result {
+            Err(e) => {
+                eprintln!("Error: {e}");
+                error_code(&e)
+            }
+            Ok(summary) if summary.headers == 0 && config.split_by != SplitBy::Size => {
+                eprintln!("No headers matched, the whole book was written as one chapter");
+                EXIT_NO_HEADERS
+            }
+            Ok(summary) => {
+                if !args.quiet {
+                    eprintln!(
+                        "Done: {} headers, {} files, {} lines",
+                        summary.headers,
+                        summary.files.len(),
+                        summary.lines
+                    );
+                    if summary.removed_lines > 0 {
+                        eprintln!("Removed {} page lines", summary.removed_lines);
+                    }
+                }
+                0
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
-mod split;
+pub mod split;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 pub use app::TemplateApp;
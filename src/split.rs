@@ -1,92 +1,1309 @@
+//! Splitting books into chapter files.
+//!
+//! A book is cut before every line matching a header regex, or at the entries of its own table of
+//! contents. Headers can form levels, such as parts made of chapters. The text before the first
+//! header and every chapter go to separate files named by a [`Naming`] template. Plain text books
+//! in any supported encoding and EPUBs can be read. Books compressed with gzip or bzip2, or packed
+//! into a ZIP archive, are unpacked into memory first. The Project Gutenberg header and license are
+//! kept apart from the chapters. [`join_folder`] puts the chapter files of a split back together.
+//!
+//! ```no_run
+//! use book_splitter::split::{split_file, Output, SplitConfig};
+//!
+//! let config = SplitConfig {
+//!     output: Output::Folder("chapters".into()),
+//!     ..SplitConfig::new(r"^Chapter \d+")
+//! };
+//! let summary = split_file(&config, "book.txt", &mut |_| {})?;
+//! println!("{} chapters", summary.chapters);
+//! # Ok::<(), book_splitter::split::SplitError>(())
+//! ```
+
 use crossbeam_channel::Sender;
-use std::path::Path;
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+mod archive;
+mod batch;
+mod detect;
+pub mod encoding;
+mod epub;
+mod fb2;
+mod gutenberg;
+mod join;
+mod manifest;
+mod markdown;
+mod naming;
+pub mod numbers;
+mod output;
+mod pages;
+pub mod presets;
+mod reflow;
+mod size;
+mod xml;
+
+pub use archive::text_entries;
+pub use batch::{output_folders, split_batch, Job};
+pub use detect::{detect_headers, Candidate};
+pub use join::{join_folder, JoinConfig, JoinSummary};
+pub use manifest::ManifestFormat;
+pub use markdown::Markdown;
+pub use naming::{NameTemplate, Naming};
+pub use output::{FolderSink, Output, Sink, ZipSink};
+pub use pages::{find_page_lines, PageLine, PageLineKind};
+pub use size::{Boundary, SizeLimit, SizeUnit};
 
+/// Errors that stop a split.
+#[derive(Debug, thiserror::Error)]
+pub enum SplitError {
+    /// The header pattern is not a valid regex.
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+    /// Reading the book or writing chapters failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Packing or unpacking a ZIP container failed.
+    #[error(transparent)]
+    Archive(#[from] zip::result::ZipError),
+    /// An XML document inside the book could not be parsed.
+    #[error(transparent)]
+    Xml(#[from] roxmltree::Error),
+    /// The file name template cannot be parsed.
+    #[error("invalid file name template: {0}")]
+    Template(String),
+    /// The book is missing a part its format requires.
+    #[error("malformed book: {0}")]
+    Malformed(String),
+    /// The chapter files of a split cannot be joined.
+    #[error("cannot join chapters: {0}")]
+    Join(String),
+}
+
+/// Progress of a split, sent while it runs.
 pub enum StatusReport {
+    /// The split has begun. Only sent by [`split_chapters`].
     Started,
+    /// The encoding the book is read with.
+    EncodingDetected(&'static Encoding),
+    /// Number of book lines processed so far.
     LinesParsed(usize),
+    /// Number of the chapter just started.
     ChaptersSplit(usize),
+    /// Header line of the chapter just started.
     NewTitle(String),
-    Error(anyhow::Error),
+    /// Something about the book looks wrong, such as chapter numbers that skip.
+    Warning(String),
+    /// The split failed. Only sent by [`split_chapters`].
+    Error(SplitError),
+    /// The split finished. Only sent by [`split_chapters`].
     Done,
+    /// The split was stopped through [`SplitConfig::cancel`]; holds the names of files already
+    /// written. Only sent by [`split_chapters`].
+    Cancelled(Vec<String>),
 }
 
-pub async fn split_chapters(
-    pattern: impl AsRef<str>,
-    file: impl AsRef<Path>,
-    folder: impl AsRef<Path>,
+/// Stops a running split before its next chapter when cancelled. Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What chapters are written as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Format {
+    /// One text file per chapter.
+    #[default]
+    Text,
+    /// A single EPUB 3 book with a chapter per document.
+    Epub,
+    /// A single FB2 book with a chapter per section.
+    Fb2,
+    /// One FB2 book per chapter.
+    Fb2Chapters,
+    /// One Markdown file per chapter, see [`Markdown`].
+    Markdown,
+}
+
+/// Information about the book for formats that carry it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Metadata {
+    pub title: String,
+    pub author: String,
+    /// BCP 47 language tag such as `en` or `ru`.
+    pub language: String,
+}
+
+impl Metadata {
+    /// These fields, with empty ones taken from `other`.
+    pub fn or(&self, other: &Metadata) -> Metadata {
+        let pick = |own: &String, other: &String| if own.is_empty() { other } else { own }.clone();
+        Metadata {
+            title: pick(&self.title, &other.title),
+            author: pick(&self.author, &other.author),
+            language: pick(&self.language, &other.language),
+        }
+    }
+
+    /// File name for single-file formats: the title made safe for the file system.
+    fn file_stem(&self) -> String {
+        let stem = naming::sanitize(&self.title.replace('/', "_"));
+        if stem.is_empty() {
+            "book".to_owned()
+        } else {
+            stem
+        }
+    }
+}
+
+/// Where chapters begin.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SplitBy {
+    /// Lines matching [`SplitConfig::pattern`].
+    #[default]
+    Pattern,
+    /// Entries of the table of contents of the book, if it has one, down to
+    /// [`SplitConfig::toc_depth`].
+    Toc,
+    /// Nowhere: the book is cut into parts by [`SplitConfig::size_limit`] alone.
+    Size,
+}
+
+/// How the headers of a level are counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Numbering {
+    /// Count on through the whole book.
+    #[default]
+    Continuous,
+    /// Count from 1 again under every header of an outer level.
+    Restart,
+}
+
+/// A level of headers above chapters, such as the parts of a book.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Level {
+    /// Regex matching the header lines of the level.
+    pub pattern: String,
+    pub numbering: Numbering,
+}
+
+/// What to do with the text before the first header or after the end marker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum MatterOutput {
+    /// Number it and write it like a chapter.
+    #[default]
+    Chapter,
+    /// Write it to a file of its own, `0000_front` or `9999_back`.
+    Separate,
+    /// Leave it out.
+    Discard,
+}
+
+/// What to do with the Project Gutenberg header and license around the text of a book.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Boilerplate {
+    /// Leave them in the text, as front and back matter.
+    Keep,
+    /// Write them to files of their own, `0000_gutenberg_header` and `9999_gutenberg_license`.
+    #[default]
+    Separate,
+    /// Leave them out.
+    Strip,
+}
+
+/// Which part of the book a chapter holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Matter {
+    /// Front matter written to a file of its own: the title page, contents and such before
+    /// the first header.
+    Front,
+    /// The chapters, along with front and back matter written as chapters.
+    #[default]
+    Body,
+    /// Back matter written to a file of its own: everything from the end marker on.
+    Back,
+    /// The Project Gutenberg header up to the start marker.
+    GutenbergHeader,
+    /// The Project Gutenberg license from the end marker on.
+    GutenbergLicense,
+}
+
+/// Settings of a split.
+#[derive(Clone, Debug)]
+pub struct SplitConfig {
+    pub split_by: SplitBy,
+    /// What to do with the Project Gutenberg header and license, in books that have them.
+    pub gutenberg: Boilerplate,
+    /// Leave out page numbers and running heads, see [`find_page_lines`].
+    pub remove_page_lines: bool,
+    /// Regex matching chapter header lines.
+    pub pattern: String,
+    /// Header levels above chapters, outermost first. A header of any level starts a new file,
+    /// and chapters lie under the last header of every outer level.
+    pub levels: Vec<Level>,
+    /// How chapters are counted.
+    pub numbering: Numbering,
+    /// Number of table of contents levels to split at, 1 for top-level entries only.
+    pub toc_depth: usize,
+    /// Number of the text before the first header. Chapters are numbered after it.
+    pub start_chapter: usize,
+    /// Cut chapters bigger than this into parts, or with [`SplitBy::Size`] the whole book.
+    pub size_limit: Option<SizeLimit>,
+    /// What to do with the text before the first header.
+    pub front_matter: MatterOutput,
+    /// Regex matching the line back matter, such as appendices or a license, starts at. Only
+    /// lines after the first header count. Empty for books without back matter.
+    pub end_marker: String,
+    /// What to do with the text from the end marker on.
+    pub back_matter: MatterOutput,
+    /// Join the lines of hard-wrapped books into paragraphs when writing chapters.
+    pub reflow: bool,
+    /// Write a manifest listing the chapters along with the files.
+    pub manifest: Option<ManifestFormat>,
+    pub naming: Naming,
+    /// Encoding of the book, detected when `None`.
+    pub encoding: Option<&'static Encoding>,
+    /// Text or FB2 file to split out of a ZIP archive holding several, see [`text_entries`].
+    pub entry: Option<String>,
+    /// Write chapters in the encoding of the book instead of UTF-8. Only used by text output.
+    pub keep_encoding: bool,
+    pub format: Format,
+    /// How [`Format::Markdown`] chapters are written.
+    pub markdown: Markdown,
+    /// Information for formats that carry it. Empty fields are taken from the book.
+    pub metadata: Metadata,
+    /// Where [`split_file`] and [`split_chapters`] write chapters.
+    pub output: Output,
+    /// Checked before every chapter is written.
+    pub cancel: CancelToken,
+}
+
+impl SplitConfig {
+    /// Default settings for splitting by `pattern` into the current folder.
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            split_by: SplitBy::Pattern,
+            gutenberg: Boilerplate::Separate,
+            remove_page_lines: false,
+            pattern: pattern.into(),
+            levels: Vec::new(),
+            numbering: Numbering::Continuous,
+            toc_depth: 1,
+            start_chapter: 1,
+            size_limit: None,
+            front_matter: MatterOutput::Chapter,
+            end_marker: String::new(),
+            back_matter: MatterOutput::Separate,
+            reflow: false,
+            manifest: None,
+            naming: Naming::default(),
+            encoding: None,
+            entry: None,
+            keep_encoding: false,
+            format: Format::Text,
+            markdown: Markdown::default(),
+            metadata: Metadata::default(),
+            output: Output::Folder(PathBuf::from(".")),
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+/// Outcome of a successful split.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    /// Number of chapters written.
+    pub chapters: usize,
+    /// Number of header lines matched.
+    pub headers: usize,
+    /// Number of book lines written.
+    pub lines: usize,
+    /// Names of the files written, in order.
+    pub files: Vec<String>,
+    /// The split was cancelled before the end of the book.
+    pub cancelled: bool,
+    /// Problems noticed in the book, see [`numbering_warnings`].
+    pub warnings: Vec<String>,
+    /// Number of page number and running head lines left out.
+    pub removed_lines: usize,
+}
+
+/// An entry of the table of contents of a book.
+#[derive(Clone, Debug)]
+pub struct TocEntry {
+    /// Zero-based index of the line the entry points to.
+    pub line: usize,
+    pub title: String,
+    /// Nesting level, zero for top-level entries.
+    pub depth: usize,
+}
+
+/// A decoded book ready to be split.
+pub struct Book {
+    /// The book text, a paragraph or a wrapped line per line.
+    pub text: String,
+    /// Encoding the book was stored in.
+    pub encoding: &'static Encoding,
+    /// Table of contents, empty for formats without one.
+    pub toc: Vec<TocEntry>,
+    /// Information found in the book, such as its Project Gutenberg header.
+    pub metadata: Metadata,
+    /// File the book was read from.
+    pub path: Option<PathBuf>,
+    /// SHA-256 of the file as unpacked from gzip, bzip2 or ZIP, so the same for `book.txt` and
+    /// `book.txt.gz`, or of the text for books not read from a file, hex encoded.
+    pub sha256: String,
+    /// The file starts with a byte order mark, which is not part of `text`.
+    pub bom: bool,
+}
+
+impl Book {
+    /// A book made of UTF-8 `text` without a table of contents.
+    pub fn from_text(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            sha256: manifest::sha256(text.as_bytes()),
+            text,
+            encoding: UTF_8,
+            toc: Vec::new(),
+            metadata: Metadata::default(),
+            path: None,
+            bom: false,
+        }
+    }
+}
+
+/// A header of an outer level that chapters lie under.
+#[derive(Clone, Copy, Debug)]
+pub struct Division<'a> {
+    /// Index of the header level, outermost first.
+    pub level: usize,
+    pub number: usize,
+    pub title: &'a str,
+}
+
+/// A part of the book that goes to one file.
+pub struct Chapter<'a> {
+    /// Number from the header if it has one, otherwise one more than the previous header of
+    /// the same level.
+    pub number: usize,
+    /// Index of the part for chapters cut by size, which share the number of the chapter.
+    pub part: Option<usize>,
+    pub matter: Matter,
+    /// Index of the header level, outermost first. Chapters have the innermost level, and so
+    /// does the text before the first header.
+    pub level: usize,
+    /// The header is of an outer level, so the chapter holds the text between it and the first
+    /// header under it.
+    pub division: bool,
+    /// Headers of outer levels the chapter lies under, outermost first.
+    pub parents: Vec<Division<'a>>,
+    /// Number read from the `number` group of the header regex.
+    pub source_number: Option<usize>,
+    /// The line that opens the chapter, `None` for text before the first header.
+    pub header: Option<&'a str>,
+    /// Title for tables of contents: the `title` group of the header regex, the table of
+    /// contents entry or else the header line.
+    pub title: Option<&'a str>,
+    /// Zero-based index of the first line of the chapter in the book.
+    pub first_line: usize,
+    pub lines: Vec<&'a str>,
+}
+
+impl Chapter<'_> {
+    /// The number padded with zeros to `width` digits, followed by a letter for parts of a
+    /// chapter cut by size: `0007a`, `0007b` and so on. Front and back matter of their own
+    /// are `front` and `back`, the Project Gutenberg header and license `pg-header` and
+    /// `pg-license`.
+    pub fn number_label(&self, width: usize) -> String {
+        match self.matter {
+            Matter::Front => "front".to_owned(),
+            Matter::Body => naming::number_label(self.number, self.part, width),
+            Matter::Back => "back".to_owned(),
+            Matter::GutenbergHeader => "pg-header".to_owned(),
+            Matter::GutenbergLicense => "pg-license".to_owned(),
+        }
+    }
+
+    /// Chapter lines, each ending with `\n`.
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.lines.iter().map(|l| l.len() + 1).sum());
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Length of the chapter text in characters.
+    pub fn char_count(&self) -> usize {
+        self.lines.iter().map(|l| l.chars().count() + 1).sum()
+    }
+}
+
+/// Where a chapter starts, as seen by [`cut_chapters`].
+struct Header<'a> {
+    level: usize,
+    title: &'a str,
+    number: Option<usize>,
+}
+
+/// Cut `lines`, book lines with their zero-based index, into chapters, each starting with a line
+/// matching one of the `levels` patterns, outermost level first and chapters last.
+///
+/// Text before the first header gets number `start_chapter`, the chapter of the n-th header
+/// gets `start_chapter + n`; headers of outer levels are counted from 1. A `number` group in a
+/// pattern overrides the count with the number in the header, written in digits, Roman
+/// numerals or words. A `title` group picks the chapter title out of the header line.
+pub fn find_chapters<'a>(
+    levels: &[(Regex, Numbering)],
+    lines: &[(usize, &'a str)],
     start_chapter: usize,
-    channel: Sender<StatusReport>,
-) {
-    channel.send(StatusReport::Started).unwrap();
-    if let Err(e) =
-        split_chapters_internal(pattern, file, folder, start_chapter, channel.clone()).await
-    {
-        channel.send(StatusReport::Error(e)).unwrap();
+) -> Vec<Chapter<'a>> {
+    let numbering: Vec<Numbering> = levels.iter().map(|(_, numbering)| *numbering).collect();
+    cut_chapters(lines, start_chapter, &numbering, |_, line| {
+        levels
+            .iter()
+            .enumerate()
+            .find_map(|(level, (pattern, _))| match_header(pattern, level, line))
+    })
+}
+
+fn match_header<'a>(pattern: &Regex, level: usize, line: &'a str) -> Option<Header<'a>> {
+    let has_groups = pattern
+        .capture_names()
+        .flatten()
+        .any(|name| name == "number" || name == "title");
+    if !has_groups {
+        return pattern.is_match(line).then_some(Header {
+            level,
+            title: line,
+            number: None,
+        });
+    }
+
+    let captures = pattern.captures(line)?;
+    let title = captures
+        .name("title")
+        .map(|m| m.as_str().trim())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(line);
+    let number = captures
+        .name("number")
+        .and_then(|m| numbers::parse_number(m.as_str()));
+    Some(Header {
+        level,
+        title,
+        number,
+    })
+}
+
+/// Cut `lines` into chapters at the lines of `toc` entries shallower than `depth`, numbered like
+/// [`find_chapters`]. Deeper entries lie under shallower ones and every level is counted by
+/// `numbering`.
+pub fn toc_chapters<'a>(
+    toc: &'a [TocEntry],
+    depth: usize,
+    numbering: Numbering,
+    lines: &[(usize, &'a str)],
+    start_chapter: usize,
+) -> Vec<Chapter<'a>> {
+    let mut entries = BTreeMap::new();
+    for entry in toc.iter().filter(|entry| entry.depth < depth) {
+        entries.entry(entry.line).or_insert(entry);
+    }
+    let numbering = vec![numbering; depth.max(1)];
+    cut_chapters(lines, start_chapter, &numbering, |line_number, _| {
+        entries.get(&line_number).map(|entry| Header {
+            level: entry.depth,
+            title: &entry.title,
+            number: None,
+        })
+    })
+}
+
+/// Compiled header regexes of `config`, outermost level first and chapters last.
+fn header_levels(config: &SplitConfig) -> Result<Vec<(Regex, Numbering)>, SplitError> {
+    let mut levels = Vec::with_capacity(config.levels.len() + 1);
+    for level in &config.levels {
+        levels.push((Regex::new(&level.pattern)?, level.numbering));
+    }
+    levels.push((Regex::new(&config.pattern)?, config.numbering));
+    Ok(levels)
+}
+
+/// The page numbers and running heads [`SplitConfig::remove_page_lines`] leaves out of `book`:
+/// those [`find_page_lines`] finds, except lines matching a header regex or the end marker.
+pub fn page_lines(config: &SplitConfig, book: &Book) -> Result<Vec<PageLine>, SplitError> {
+    let mut keep: Vec<Regex> = match config.split_by {
+        SplitBy::Pattern => header_levels(config)?
+            .into_iter()
+            .map(|(pattern, _)| pattern)
+            .collect(),
+        SplitBy::Toc | SplitBy::Size => Vec::new(),
+    };
+    if !config.end_marker.is_empty() {
+        keep.push(Regex::new(&config.end_marker)?);
+    }
+    let lines: Vec<&str> = book.text.lines().collect();
+    Ok(find_page_lines(&book.text)
+        .into_iter()
+        .filter(|page_line| {
+            let line = lines[page_line.line];
+            !keep.iter().any(|pattern| pattern.is_match(line))
+        })
+        .collect())
+}
+
+/// Cut `book` into chapters as `config` says.
+pub fn chapters<'a>(config: &SplitConfig, book: &'a Book) -> Result<Vec<Chapter<'a>>, SplitError> {
+    let boundaries = match config.gutenberg {
+        Boilerplate::Keep => None,
+        Boilerplate::Separate | Boilerplate::Strip => gutenberg::boundaries(&book.text),
+    };
+    let body = boundaries.map_or(0..usize::MAX, |b| b.start..b.end);
+    let removed: HashSet<usize> = if config.remove_page_lines {
+        page_lines(config, book)?
+            .into_iter()
+            .map(|page_line| page_line.line)
+            .collect()
     } else {
-        channel.send(StatusReport::Done).unwrap();
+        HashSet::new()
+    };
+    let lines: Vec<(usize, &str)> = book
+        .text
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| body.contains(i) && !removed.contains(i))
+        .collect();
+
+    let mut chapters = match config.split_by {
+        SplitBy::Pattern => find_chapters(&header_levels(config)?, &lines, config.start_chapter),
+        SplitBy::Toc => toc_chapters(
+            &book.toc,
+            config.toc_depth,
+            config.numbering,
+            &lines,
+            config.start_chapter,
+        ),
+        SplitBy::Size => cut_chapters(&lines, config.start_chapter, &[], |_, _| None),
+    };
+    cut_back_matter(config, &mut chapters)?;
+    if config.split_by != SplitBy::Size && chapters.iter().any(|c| c.header.is_some()) {
+        let front = chapters.first().filter(|c| c.header.is_none()).is_some();
+        if front && config.front_matter != MatterOutput::Chapter {
+            // Chapters count from `start_chapter` without the front matter among them.
+            let (level, mut previous) = (chapters[0].level, chapters[0].number);
+            for chapter in chapters[1..].iter_mut().filter(|c| c.level == level) {
+                if chapter.number != previous + 1 {
+                    break;
+                }
+                previous = chapter.number;
+                chapter.number -= 1;
+            }
+            if config.front_matter == MatterOutput::Separate {
+                chapters[0].matter = Matter::Front;
+            } else {
+                chapters.remove(0);
+            }
+        } else if front {
+            // Chapters numbered by their headers start at their own numbers, likely the one
+            // the text before them got, so it goes before `start_chapter`.
+            let level = chapters[0].level;
+            let numbered = chapters[1..]
+                .iter()
+                .any(|c| c.level == level && c.source_number.is_some());
+            if numbered {
+                chapters[0].number = config.start_chapter.saturating_sub(1);
+            }
+        }
     }
+    if let Some(limit) = &config.size_limit {
+        chapters = chapters
+            .into_iter()
+            .flat_map(|chapter| match chapter.matter {
+                Matter::Body => size::cut_by_size(chapter, limit),
+                _ => vec![chapter],
+            })
+            .collect();
+    }
+    if config.split_by == SplitBy::Size {
+        // Parts of a book without headers are chapters of their own.
+        let body = chapters.iter_mut().filter(|c| c.matter == Matter::Body);
+        for (i, chapter) in body.enumerate() {
+            chapter.number = config.start_chapter + i;
+            chapter.part = None;
+        }
+    }
+
+    if let Some(b) = boundaries.filter(|_| config.gutenberg == Boilerplate::Separate) {
+        let lines: Vec<&str> = book.text.lines().collect();
+        let boilerplate = |matter, title, first_line, lines: &[&'a str]| Chapter {
+            number: 0,
+            part: None,
+            matter,
+            level: 0,
+            division: false,
+            parents: Vec::new(),
+            source_number: None,
+            header: None,
+            title: Some(title),
+            first_line,
+            lines: lines.to_vec(),
+        };
+        chapters.insert(
+            0,
+            boilerplate(
+                Matter::GutenbergHeader,
+                "Project Gutenberg header",
+                0,
+                &lines[..b.start],
+            ),
+        );
+        if b.end < lines.len() {
+            chapters.push(boilerplate(
+                Matter::GutenbergLicense,
+                "Project Gutenberg license",
+                b.end,
+                &lines[b.end..],
+            ));
+        }
+    }
+    Ok(chapters)
 }
 
-async fn split_chapters_internal(
-    pattern: impl AsRef<str>,
-    file: impl AsRef<Path>,
-    folder: impl AsRef<Path>,
+/// Move everything from the first line after the first header that matches
+/// `config.end_marker` into a back matter chapter, or drop it, as `config.back_matter` says.
+fn cut_back_matter<'a>(
+    config: &SplitConfig,
+    chapters: &mut Vec<Chapter<'a>>,
+) -> Result<(), SplitError> {
+    if config.end_marker.is_empty() {
+        return Ok(());
+    }
+    let marker = Regex::new(&config.end_marker)?;
+    // Without a header there is nothing for the marker to come after.
+    let Some(first_header) = chapters.iter().position(|c| c.header.is_some()) else {
+        return Ok(());
+    };
+    let found = chapters
+        .iter()
+        .enumerate()
+        .skip(first_header)
+        .find_map(|(i, chapter)| {
+            // The first header itself is never the marker.
+            let skip = usize::from(i == first_header);
+            let line = chapter
+                .lines
+                .iter()
+                .skip(skip)
+                .position(|l| marker.is_match(l))?;
+            Some((i, line + skip))
+        });
+    let Some((index, line)) = found else {
+        return Ok(());
+    };
+
+    let first_line = chapters[index].first_line + line;
+    let mut lines = chapters[index].lines.split_off(line);
+    let keep = if chapters[index].lines.is_empty() {
+        index
+    } else {
+        index + 1
+    };
+    for chapter in chapters.drain(keep..) {
+        lines.extend(chapter.lines);
+    }
+
+    let level = chapters.iter().map(|c| c.level).max().unwrap_or(0);
+    let number = chapters
+        .iter()
+        .rfind(|c| c.level == level)
+        .map_or(config.start_chapter, |c| c.number + 1);
+    let matter = match config.back_matter {
+        MatterOutput::Chapter => Matter::Body,
+        MatterOutput::Separate => Matter::Back,
+        MatterOutput::Discard => return Ok(()),
+    };
+    chapters.push(Chapter {
+        number,
+        part: None,
+        matter,
+        level,
+        division: false,
+        parents: Vec::new(),
+        source_number: None,
+        header: Some(lines[0]),
+        title: Some(lines[0].trim()),
+        first_line,
+        lines,
+    });
+    Ok(())
+}
+
+/// Start a new chapter at every line `header` returns a header for. `numbering` has an entry
+/// per level, the last one for chapters.
+fn cut_chapters<'a>(
+    lines: &[(usize, &'a str)],
     start_chapter: usize,
-    channel: Sender<StatusReport>,
-) -> anyhow::Result<()> {
-    use regex::Regex;
-    use tokio::fs::File;
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    numbering: &[Numbering],
+    mut header: impl FnMut(usize, &'a str) -> Option<Header<'a>>,
+) -> Vec<Chapter<'a>> {
+    let chapter_level = numbering.len().saturating_sub(1);
+    let mut counters = vec![0; chapter_level + 1];
+    counters[chapter_level] = start_chapter;
+    let mut parents: Vec<Division<'a>> = Vec::new();
+
+    let mut chapters = Vec::new();
+    let mut current = Chapter {
+        number: start_chapter,
+        part: None,
+        matter: Matter::Body,
+        level: chapter_level,
+        division: false,
+        parents: Vec::new(),
+        source_number: None,
+        header: None,
+        title: None,
+        first_line: lines.first().map_or(0, |&(line_number, _)| line_number),
+        lines: Vec::new(),
+    };
+
+    for &(line_number, line) in lines {
+        if let Some(Header {
+            level,
+            title,
+            number,
+        }) = header(line_number, line)
+        {
+            for deeper in level + 1..counters.len() {
+                if numbering[deeper] == Numbering::Restart {
+                    counters[deeper] = 0;
+                }
+            }
+            counters[level] = number.unwrap_or(counters[level] + 1);
+            parents.retain(|parent| parent.level < level);
 
+            let chapter = Chapter {
+                number: counters[level],
+                part: None,
+                matter: Matter::Body,
+                level,
+                division: level < chapter_level,
+                parents: parents.clone(),
+                source_number: number,
+                header: Some(line),
+                title: Some(title),
+                first_line: line_number,
+                lines: Vec::new(),
+            };
+            if chapter.division {
+                parents.push(Division {
+                    level,
+                    number: chapter.number,
+                    title,
+                });
+            }
+            let previous = std::mem::replace(&mut current, chapter);
+            if !previous.lines.is_empty() {
+                chapters.push(previous);
+            }
+        }
+        current.lines.push(line);
+    }
+
+    if !current.lines.is_empty() {
+        chapters.push(current);
+    }
+    chapters
+}
+
+/// Describe numbers taken from headers that repeat, go backwards or skip some, comparing each
+/// header with the previous one of its level. Counting from 1 again under a new header of an
+/// outer level is fine.
+pub fn numbering_warnings(chapters: &[Chapter<'_>]) -> Vec<String> {
+    let levels = chapters.iter().map(|c| c.level + 1).max().unwrap_or(0);
+    let mut previous = vec![None; levels];
+    let mut restarted = vec![false; levels];
+    let mut warnings = Vec::new();
+    for chapter in chapters.iter().filter(|c| c.header.is_some()) {
+        let level = chapter.level;
+        for deeper in &mut restarted[level + 1..] {
+            *deeper = true;
+        }
+        let Some(number) = chapter.source_number else {
+            continue;
+        };
+        let line = chapter.first_line + 1;
+        let previous = previous[level].replace(number);
+        let restart = std::mem::take(&mut restarted[level]);
+        match previous {
+            _ if restart && number <= 1 => {}
+            Some(previous) if number == previous => {
+                warnings.push(format!("Line {line}: number {number} repeats"));
+            }
+            Some(previous) if number < previous => {
+                warnings.push(format!(
+                    "Line {line}: number {number} goes backwards after {previous}"
+                ));
+            }
+            Some(previous) if number > previous + 1 => {
+                warnings.push(format!(
+                    "Line {line}: number {number} follows {previous}, skipping {}",
+                    if number == previous + 2 {
+                        (previous + 1).to_string()
+                    } else {
+                        format!("{}-{}", previous + 1, number - 1)
+                    }
+                ));
+            }
+            _ => {}
+        }
+    }
+    warnings
+}
+
+/// Read a book from disk and decode it, detecting the encoding of text unless one is given.
+///
+/// EPUB and FB2 books, FB2 also zipped, are recognized by their content or extension and
+/// flattened to text. Books compressed with gzip or bzip2 are decompressed as they are read,
+/// and the text file `entry` or else the only one is taken out of a ZIP archive. Title, author
+/// and language are taken from a Project Gutenberg header.
+pub fn read_book(
+    file: impl AsRef<Path>,
+    encoding: Option<&'static Encoding>,
+    entry: Option<&str>,
+) -> Result<Book, SplitError> {
     let file = file.as_ref();
-    let folder = folder.as_ref();
-    let pattern = pattern.as_ref();
-    let pattern = Regex::new(pattern)?;
+    let mut reader = std::io::BufReader::new(std::fs::File::open(file)?);
+    if let Some((bytes, name)) = archive::unpack(&mut reader, file, entry)? {
+        return decode(&bytes, &name, file, encoding);
+    }
+    reader.rewind()?;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    decode(&bytes, file, file, encoding)
+}
 
-    tokio::fs::create_dir_all(folder).await?;
-    let file = File::open(file).await?;
-    let reader = BufReader::new(file);
+/// [`read_book`] for a book already in memory, such as one uploaded to the browser. `file` is
+/// the name it came with.
+pub fn decode_book(
+    bytes: &[u8],
+    file: &Path,
+    encoding: Option<&'static Encoding>,
+    entry: Option<&str>,
+) -> Result<Book, SplitError> {
+    match archive::unpack(std::io::Cursor::new(bytes), file, entry)? {
+        Some((bytes, name)) => decode(&bytes, &name, file, encoding),
+        None => decode(bytes, file, file, encoding),
+    }
+}
 
-    let mut chapter_number = start_chapter;
-    let mut line_number = 0usize;
-    let mut chapter_text = String::new();
+/// Decode the unpacked `bytes` of the book at `file`, recognizing its format by the `name` it
+/// has unpacked.
+fn decode(
+    bytes: &[u8],
+    name: &Path,
+    file: &Path,
+    encoding: Option<&'static Encoding>,
+) -> Result<Book, SplitError> {
+    let mut book = if epub::is_epub(bytes) || has_extension(name, "epub") {
+        epub::read_epub(bytes)?
+    } else if fb2::is_fb2(bytes) || has_extension(name, "fb2") {
+        fb2::read_fb2(bytes)?
+    } else {
+        let encoding = encoding.unwrap_or_else(|| encoding::detect(bytes));
+        Book {
+            text: encoding::decode(bytes, encoding).into_owned(),
+            encoding,
+            toc: Vec::new(),
+            metadata: Metadata::default(),
+            path: None,
+            sha256: String::new(),
+            bom: encoding::has_bom(bytes, encoding),
+        }
+    };
+    book.metadata = gutenberg::metadata(&book.text);
+    book.path = Some(file.to_owned());
+    book.sha256 = manifest::sha256(bytes);
+    Ok(book)
+}
 
-    let mut lines = reader.lines();
-    while let Some(line) = lines.next_line().await? {
-        if pattern.is_match(&line) {
-            channel.send(StatusReport::NewTitle(line.clone())).unwrap();
+fn has_extension(file: &Path, extension: &str) -> bool {
+    file.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Split an already read `book` into `sink`.
+///
+/// `config.output` and `config.encoding` are ignored; with `config.keep_encoding` chapters are
+/// encoded back into the encoding of the book. `progress` gets reports as chapters are written.
+pub fn split_book(
+    config: &SplitConfig,
+    book: &Book,
+    sink: &mut dyn Sink,
+    progress: &mut dyn FnMut(StatusReport),
+) -> Result<Summary, SplitError> {
+    let output_encoding = if config.keep_encoding {
+        book.encoding
+    } else {
+        UTF_8
+    };
 
-            // Write the previous chapter text to file, if any
-            if !chapter_text.is_empty() {
-                let filename = folder.join(format!("{:04}.txt", chapter_number));
-                let mut file = File::create(filename).await?;
-                file.write_all(chapter_text.as_bytes()).await?;
-                chapter_text.clear();
+    let metadata = config.metadata.or(&book.metadata);
+    let levels = match config.split_by {
+        SplitBy::Pattern => header_levels(config)?,
+        SplitBy::Toc | SplitBy::Size => Vec::new(),
+    };
+    let headers: Vec<Regex> = levels.iter().map(|(pattern, _)| pattern.clone()).collect();
+    let mut names = config.naming.compile(&headers)?;
+
+    let mut summary = Summary {
+        chapters: 0,
+        headers: 0,
+        lines: 0,
+        files: Vec::new(),
+        cancelled: false,
+        warnings: Vec::new(),
+        removed_lines: 0,
+    };
+    if config.remove_page_lines {
+        summary.removed_lines = page_lines(config, book)?.len();
+    }
+    let wrap_width = config
+        .reflow
+        .then(|| reflow::wrap_width(&book.text))
+        .flatten();
+    let chapters = chapters(config, book)?;
+    for warning in numbering_warnings(&chapters) {
+        progress(StatusReport::Warning(warning.clone()));
+        summary.warnings.push(warning);
+    }
+    let mut epub = match config.format {
+        Format::Epub => Some(epub::EpubBuilder::new(&metadata, &book.sha256)),
+        _ => None,
+    };
+    let mut fb2 = match config.format {
+        Format::Fb2 => Some(fb2::Fb2Builder::new(&metadata)),
+        _ => None,
+    };
+    let mut manifest = config
+        .manifest
+        .map(|format| manifest::ManifestBuilder::new(book, format));
+    // Named up front, so that Markdown chapters can link to the next one.
+    let files: Vec<String> = chapters
+        .iter()
+        .map(|chapter| {
+            let captures = levels
+                .get(chapter.level)
+                .zip(chapter.header)
+                .and_then(|((pattern, _), header)| pattern.captures(header));
+            // Single-file formats name the book once all chapters are in.
+            match config.format {
+                Format::Text => names.file_name(chapter, captures.as_ref(), "txt"),
+                Format::Fb2Chapters => names.file_name(chapter, captures.as_ref(), "fb2"),
+                Format::Markdown => names.file_name(chapter, captures.as_ref(), "md"),
+                Format::Epub | Format::Fb2 => String::new(),
             }
+        })
+        .collect();
+    let book_name = Some(metadata.title.clone())
+        .filter(|title| !title.is_empty())
+        .or_else(|| {
+            let stem = book.path.as_ref()?.file_stem()?;
+            Some(stem.to_string_lossy().into_owned())
+        });
+    for (i, chapter) in chapters.into_iter().enumerate() {
+        if config.cancel.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+
+        if let Some(header) = chapter.header {
+            summary.headers += 1;
+            progress(StatusReport::NewTitle(header.to_owned()));
+            progress(StatusReport::ChaptersSplit(chapter.number));
+        }
 
-            chapter_number += 1;
-            channel
-                .send(StatusReport::ChaptersSplit(chapter_number))
-                .unwrap();
+        let name = files[i].clone();
+        if let Some(manifest) = &mut manifest {
+            manifest.add_chapter(&chapter, &name);
+        }
+        let end_line = chapter.first_line + chapter.lines.len();
+        let paragraphs: Vec<String>;
+        let chapter = match wrap_width {
+            Some(width) => {
+                // The header stays a line of its own.
+                let skip = usize::from(chapter.header.is_some());
+                paragraphs = reflow::reflow(&chapter.lines[skip..], width);
+                Chapter {
+                    lines: chapter.lines[..skip]
+                        .iter()
+                        .copied()
+                        .chain(paragraphs.iter().map(String::as_str))
+                        .collect(),
+                    ..chapter
+                }
+            }
+            None => chapter,
+        };
+        match config.format {
+            Format::Text => {
+                let text = chapter.text();
+                let contents = encoding::encode(&text, output_encoding);
+                sink.write(&name, &contents)?;
+                summary.files.push(name);
+            }
+            Format::Fb2Chapters => {
+                let document = fb2::Fb2Builder::chapter_document(&metadata, &chapter);
+                sink.write(&name, document.as_bytes())?;
+                summary.files.push(name);
+            }
+            Format::Markdown => {
+                let document = markdown::chapter_document(
+                    &config.markdown,
+                    &chapter,
+                    book_name.as_deref(),
+                    &name,
+                    i.checked_sub(1).map(|prev| files[prev].as_str()),
+                    files.get(i + 1).map(String::as_str),
+                );
+                sink.write(&name, document.as_bytes())?;
+                summary.files.push(name);
+            }
+            Format::Epub => {
+                if let Some(book) = &mut epub {
+                    book.add_chapter(&chapter);
+                }
+            }
+            Format::Fb2 => {
+                if let Some(book) = &mut fb2 {
+                    book.add_chapter(&chapter);
+                }
+            }
         }
-        // Append the line to the current chapter text
-        chapter_text.push_str(&line);
-        chapter_text.push('\n');
+        summary.chapters += 1;
 
-        line_number += 1;
-        if line_number % 1000 == 0 {
-            channel
-                .send(StatusReport::LinesParsed(line_number))
-                .unwrap();
+        summary.lines = end_line;
+        progress(StatusReport::LinesParsed(summary.lines));
+    }
+
+    if let Some(book) = epub.filter(|_| !summary.cancelled) {
+        let name = format!("{}.epub", metadata.file_stem());
+        sink.write(&name, &book.finish()?)?;
+        summary.files.push(name);
+    }
+    if let Some(book) = fb2.filter(|_| !summary.cancelled) {
+        let name = format!("{}.fb2", metadata.file_stem());
+        sink.write(&name, book.finish().as_bytes())?;
+        summary.files.push(name);
+    }
+    if let Some(mut manifest) = manifest.filter(|_| !summary.cancelled) {
+        if let Some(book) = summary.files.last() {
+            manifest.set_book_file(book);
         }
+        let name = manifest.format.file_name().to_owned();
+        sink.write(&name, &manifest.finish(config))?;
+        summary.files.push(name);
     }
+    sink.finish()?;
+
+    Ok(summary)
+}
 
-    // Write the last chapter text to file, if any
-    if !chapter_text.is_empty() {
-        let filename = folder.join(format!("{:04}.txt", chapter_number));
-        let mut file = File::create(filename).await?;
-        file.write_all(chapter_text.as_bytes()).await?;
+/// Split the book at `file` into `config.output`.
+pub fn split_file(
+    config: &SplitConfig,
+    file: impl AsRef<Path>,
+    progress: &mut dyn FnMut(StatusReport),
+) -> Result<Summary, SplitError> {
+    // Fail on bad settings before touching the disk.
+    let headers: Vec<Regex> = match config.split_by {
+        SplitBy::Pattern => header_levels(config)?
+            .into_iter()
+            .map(|(pattern, _)| pattern)
+            .collect(),
+        SplitBy::Toc | SplitBy::Size => Vec::new(),
+    };
+    if !config.end_marker.is_empty() {
+        Regex::new(&config.end_marker)?;
+    }
+    config.naming.compile(&headers)?;
+    // A split cancelled before it starts neither reads the book nor creates the output.
+    if config.cancel.is_cancelled() {
+        return Ok(Summary {
+            cancelled: true,
+            ..Summary::default()
+        });
     }
+    let book = read_book(file, config.encoding, config.entry.as_deref())?;
+    progress(StatusReport::EncodingDetected(book.encoding));
+    let mut sink = config.output.open()?;
+    split_book(config, &book, sink.as_mut(), progress)
+}
 
-    Ok(())
+/// Run [`split_file`] on a blocking thread, reporting everything through `channel`.
+///
+/// The channel gets [`StatusReport::Started`] first and one of [`StatusReport::Done`],
+/// [`StatusReport::Cancelled`] or [`StatusReport::Error`] last.
+pub async fn split_chapters(config: SplitConfig, file: PathBuf, channel: Sender<StatusReport>) {
+    run_split(config, file, move |report| channel.send(report).unwrap()).await
+}
+
+/// [`split_chapters`] with every report handed to `send`.
+async fn run_split(
+    config: SplitConfig,
+    file: PathBuf,
+    send: impl Fn(StatusReport) + Clone + Send + 'static,
+) {
+    send(StatusReport::Started);
+    let mut progress = send.clone();
+    let result = tokio::task::spawn_blocking(move || split_file(&config, file, &mut progress))
+        .await
+        .expect("split task panicked");
+    match result {
+        Ok(summary) if summary.cancelled => send(StatusReport::Cancelled(summary.files)),
+        Ok(_) => send(StatusReport::Done),
+        Err(e) => send(StatusReport::Error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chapter `number` made of `lines`, opened by the first one as its header.
+    pub fn chapter<'a>(number: usize, lines: &[&'a str]) -> Chapter<'a> {
+        Chapter {
+            number,
+            part: None,
+            matter: Matter::Body,
+            level: 0,
+            division: false,
+            parents: Vec::new(),
+            source_number: None,
+            header: Some(lines[0]),
+            title: Some(lines[0]),
+            first_line: 0,
+            lines: lines.to_vec(),
+        }
+    }
+
+    #[test]
+    fn page_lines_keep_headers() {
+        // A chapter per page, so headers come back exactly as often as running heads would.
+        let mut text = String::new();
+        for page in 1..=8 {
+            text.push_str(&format!("Chapter {page}\n"));
+            for line in 2..30 {
+                text.push_str(&format!("Line {line} of page {page}.\n"));
+            }
+            text.push_str(&format!("{page}\n"));
+        }
+        let book = Book::from_text(&text);
+        let config = SplitConfig {
+            remove_page_lines: true,
+            ..SplitConfig::new(r"^Chapter \d+$")
+        };
+        let found = page_lines(&config, &book).unwrap();
+        assert_eq!(found.len(), 8);
+        assert!(found
+            .iter()
+            .all(|page_line| page_line.kind == PageLineKind::PageNumber));
+        let chapters = chapters(&config, &book).unwrap();
+        assert_eq!(chapters.iter().filter(|c| c.header.is_some()).count(), 8);
+    }
+
+    fn numbers(config: &SplitConfig, text: &str) -> Vec<(usize, Matter)> {
+        chapters(config, &Book::from_text(text))
+            .unwrap()
+            .iter()
+            .map(|chapter| (chapter.number, chapter.matter))
+            .collect()
+    }
+
+    #[test]
+    fn front_matter_goes_before_numbered_chapters() {
+        let text = "A preface.\nChapter 1\nOne.\nChapter 2\nTwo.\n";
+        let numbered = SplitConfig::new(r"^Chapter (?P<number>\d+)$");
+        assert_eq!(
+            numbers(&numbered, text),
+            [(0, Matter::Body), (1, Matter::Body), (2, Matter::Body)]
+        );
+        let separate = SplitConfig {
+            front_matter: MatterOutput::Separate,
+            ..numbered.clone()
+        };
+        assert_eq!(
+            numbers(&separate, text),
+            [(1, Matter::Front), (1, Matter::Body), (2, Matter::Body)]
+        );
+        // Counted chapters follow the text before them.
+        let counted = SplitConfig::new(r"^Chapter \d+$");
+        assert_eq!(
+            numbers(&counted, text),
+            [(1, Matter::Body), (2, Matter::Body), (3, Matter::Body)]
+        );
+    }
+
+    #[test]
+    fn back_matter_starts_after_the_first_header() {
+        let config = SplitConfig {
+            end_marker: "^THE END$".to_owned(),
+            back_matter: MatterOutput::Separate,
+            ..SplitConfig::new(r"^Chapter \d+$")
+        };
+        let text = "THE END\nChapter 1\nOne.\nChapter 2\nTwo.\nTHE END\nNotes.\n";
+        assert_eq!(
+            numbers(&config, text),
+            [
+                (1, Matter::Body),
+                (2, Matter::Body),
+                (3, Matter::Body),
+                (4, Matter::Back)
+            ]
+        );
+        // A book without headers has no back matter to cut.
+        let text = "Text.\nTHE END\nNotes.\n";
+        assert_eq!(numbers(&config, text), [(1, Matter::Body)]);
+    }
+
+    #[test]
+    fn numbered_chapters_get_distinct_file_names() {
+        let book = Book::from_text("A preface.\nChapter 1\nOne.\nChapter 2\nTwo.\n");
+        let config = SplitConfig::new(r"^Chapter (?P<number>\d+)$");
+        let mut files = Vec::new();
+        split_book(&config, &book, &mut files, &mut |_| {}).unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["0000.txt", "0001.txt", "0002.txt"]);
+    }
+
+    #[test]
+    fn cancelled_split_leaves_the_disk_alone() {
+        let dir = std::env::temp_dir().join(format!("book_splitter_cancel_{}", std::process::id()));
+        let config = SplitConfig {
+            output: Output::Folder(dir.join("chapters")),
+            ..SplitConfig::new(r"^Chapter \d+$")
+        };
+        config.cancel.cancel();
+        // The book is never read, so it need not exist.
+        let summary = split_file(&config, dir.join("missing.txt"), &mut |_| {}).unwrap();
+        assert!(summary.cancelled);
+        assert!(summary.files.is_empty());
+        assert!(!dir.exists());
+    }
 }
@@ -0,0 +1,224 @@
+//! Books packed into gzip, bzip2 or ZIP files, unpacked into memory before they are decoded.
+
+use super::SplitError;
+use bzip2::read::BzDecoder;
+use flate2::read::MultiGzDecoder;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// Unpack the book in `reader`, named `file`, if it is compressed or a ZIP archive of text or
+/// FB2 files. `entry` picks the file of an archive holding several.
+///
+/// Returns the book with the name it has unpacked, such as `book.txt` for `book.txt.gz` or
+/// `book.fb2` for `book.fb2.zip`, or `None` for anything else, EPUBs included. `reader` is left
+/// anywhere.
+pub fn unpack<R: Read + Seek>(
+    mut reader: R,
+    file: &Path,
+    entry: Option<&str>,
+) -> Result<Option<(Vec<u8>, PathBuf)>, SplitError> {
+    let mut magic = Vec::with_capacity(4);
+    (&mut reader).take(4).read_to_end(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut bytes = Vec::new();
+    if magic.starts_with(b"\x1f\x8b") {
+        MultiGzDecoder::new(reader).read_to_end(&mut bytes)?;
+        Ok(Some((bytes, without_extension(file, &["gz", "gzip"]))))
+    } else if magic.starts_with(b"BZh") {
+        BzDecoder::new(reader).read_to_end(&mut bytes)?;
+        Ok(Some((bytes, without_extension(file, &["bz2", "bzip2"]))))
+    } else if magic.starts_with(b"PK\x03\x04") {
+        let mut archive = ZipArchive::new(reader)?;
+        if is_epub(&mut archive) {
+            return Ok(None);
+        }
+        let names = text_names(&mut archive)?;
+        let name = match (entry, names.as_slice()) {
+            (Some(entry), _) if names.iter().any(|name| name == entry) => entry.to_owned(),
+            (Some(entry), _) => {
+                return Err(SplitError::Malformed(format!("no {entry} in the archive")));
+            }
+            (None, [name]) => name.clone(),
+            (None, []) => {
+                return Err(SplitError::Malformed(
+                    "no text or FB2 file in the archive".to_owned(),
+                ));
+            }
+            (None, names) => {
+                return Err(SplitError::Malformed(format!(
+                    "the archive holds several books, choose one of: {}",
+                    names.join(", ")
+                )));
+            }
+        };
+        archive.by_name(&name)?.read_to_end(&mut bytes)?;
+        Ok(Some((bytes, PathBuf::from(name))))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The text and FB2 files of a ZIP archive to choose from, in the order they are stored. Empty
+/// for anything that is not a ZIP archive, or is an EPUB.
+pub fn text_entries<R: Read + Seek>(mut reader: R) -> Result<Vec<String>, SplitError> {
+    let mut magic = Vec::with_capacity(4);
+    (&mut reader).take(4).read_to_end(&mut magic)?;
+    if magic != b"PK\x03\x04" {
+        return Ok(Vec::new());
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    let mut archive = ZipArchive::new(reader)?;
+    if is_epub(&mut archive) {
+        return Ok(Vec::new());
+    }
+    text_names(&mut archive)
+}
+
+/// Names of the `.txt` and `.fb2` files of `archive` in the order they are stored, leaving out
+/// the metadata macOS adds.
+fn text_names<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<String>, SplitError> {
+    let mut names = Vec::new();
+    for i in 0..archive.len() {
+        let name = archive.by_index_raw(i)?.name().to_owned();
+        let hidden = name.starts_with("__MACOSX/")
+            || name.rsplit('/').next().unwrap_or(&name).starts_with("._");
+        let lowercase = name.to_lowercase();
+        if (lowercase.ends_with(".txt") || lowercase.ends_with(".fb2")) && !hidden {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// The archive is itself a book, an EPUB.
+fn is_epub<R: Read + Seek>(archive: &mut ZipArchive<R>) -> bool {
+    let mut mimetype = Vec::new();
+    archive
+        .by_name("mimetype")
+        .is_ok_and(|mut file| file.read_to_end(&mut mimetype).is_ok())
+        && mimetype == b"application/epub+zip"
+}
+
+/// `file` without its last extension if it is one of `extensions`.
+fn without_extension(file: &Path, extensions: &[&str]) -> PathBuf {
+    match file.extension() {
+        Some(extension)
+            if extensions
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known)) =>
+        {
+            file.with_extension("")
+        }
+        _ => file.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, Default::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn unpacked(bytes: &[u8], file: &str, entry: Option<&str>) -> Option<(String, PathBuf)> {
+        unpack(Cursor::new(bytes), Path::new(file), entry)
+            .unwrap()
+            .map(|(bytes, name)| (String::from_utf8(bytes).unwrap(), name))
+    }
+
+    #[test]
+    fn unpacks_gzip_and_bzip2() {
+        let book = Some(("Chapter 1\n".to_owned(), PathBuf::from("book.txt")));
+        assert_eq!(unpacked(&gzip("Chapter 1\n"), "book.txt.gz", None), book);
+
+        let mut members = gzip("Chapter 1\n");
+        members.extend(gzip("Chapter 2\n"));
+        assert_eq!(
+            unpacked(&members, "book.txt.gz", None),
+            Some((
+                "Chapter 1\nChapter 2\n".to_owned(),
+                PathBuf::from("book.txt")
+            ))
+        );
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(b"Chapter 1\n").unwrap();
+        assert_eq!(
+            unpacked(&encoder.finish().unwrap(), "book.txt.bz2", None),
+            book
+        );
+    }
+
+    #[test]
+    fn unpacks_the_only_book_of_an_archive() {
+        let bytes = zip(&[
+            ("book/book.txt", "Chapter 1\n"),
+            ("__MACOSX/book/._book.txt", "metadata"),
+            ("book/cover.jpg", ""),
+        ]);
+        assert_eq!(
+            unpacked(&bytes, "book.zip", None),
+            Some(("Chapter 1\n".to_owned(), PathBuf::from("book/book.txt")))
+        );
+
+        let bytes = zip(&[("book.fb2", "<FictionBook/>")]);
+        assert_eq!(
+            unpacked(&bytes, "book.fb2.zip", None),
+            Some(("<FictionBook/>".to_owned(), PathBuf::from("book.fb2")))
+        );
+    }
+
+    #[test]
+    fn asks_for_an_entry_of_an_archive_holding_several_books() {
+        let bytes = zip(&[("a.txt", "text"), ("b.fb2", "<FictionBook/>")]);
+        assert_eq!(
+            text_entries(Cursor::new(&bytes)).unwrap(),
+            ["a.txt", "b.fb2"]
+        );
+        match unpack(Cursor::new(&bytes), Path::new("books.zip"), None) {
+            Err(SplitError::Malformed(message)) => assert!(message.ends_with("a.txt, b.fb2")),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(
+            unpacked(&bytes, "books.zip", Some("b.fb2")),
+            Some(("<FictionBook/>".to_owned(), PathBuf::from("b.fb2")))
+        );
+        assert!(unpack(Cursor::new(&bytes), Path::new("books.zip"), Some("c.txt")).is_err());
+        assert!(unpack(
+            Cursor::new(zip(&[("cover.jpg", "")])),
+            Path::new("a.zip"),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn leaves_epubs_and_plain_books_alone() {
+        let epub = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("OEBPS/chapter.txt", "text"),
+        ]);
+        assert_eq!(unpacked(&epub, "book.epub", None), None);
+        assert!(text_entries(Cursor::new(&epub)).unwrap().is_empty());
+        assert_eq!(unpacked(b"Chapter 1\n", "book.txt", None), None);
+        assert!(text_entries(Cursor::new(b"Chapter 1\n"))
+            .unwrap()
+            .is_empty());
+    }
+}
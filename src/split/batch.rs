@@ -0,0 +1,122 @@
+//! Splitting a queue of books, a few at a time.
+
+use super::{naming, SplitConfig, StatusReport};
+use crossbeam_channel::Sender;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A book of a batch with the settings to split it with.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub book: PathBuf,
+    pub config: SplitConfig,
+}
+
+/// Folders for the chapters of `books` under `root`, named after the books and kept apart when
+/// books share a name.
+pub fn output_folders(root: &Path, books: &[PathBuf]) -> Vec<PathBuf> {
+    let mut used = HashSet::new();
+    books
+        .iter()
+        .map(|book| {
+            let stem = book
+                .file_stem()
+                .map(|stem| naming::sanitize(&stem.to_string_lossy()))
+                .filter(|stem| !stem.is_empty())
+                .unwrap_or_else(|| "book".to_owned());
+            let mut name = stem.clone();
+            let mut counter = 1;
+            while !used.insert(name.to_lowercase()) {
+                counter += 1;
+                name = format!("{stem}-{counter}");
+            }
+            root.join(name)
+        })
+        .collect()
+}
+
+/// Run [`split_file`](super::split_file) for every job, at most `concurrency` at once, reporting
+/// through `channel` along with the index of the job.
+///
+/// Every job gets [`StatusReport::Started`] when it leaves the queue and one of
+/// [`StatusReport::Done`], [`StatusReport::Cancelled`] or [`StatusReport::Error`] last, like
+/// [`split_chapters`](super::split_chapters).
+pub async fn split_batch(
+    jobs: Vec<Job>,
+    concurrency: usize,
+    channel: Sender<(usize, StatusReport)>,
+) {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = Vec::new();
+    for (i, job) in jobs.into_iter().enumerate() {
+        // Waiting here rather than in the task starts books in the order of the queue.
+        let permit = permits.clone().acquire_owned().await.unwrap();
+        if job.config.cancel.is_cancelled() {
+            // Books still queued when the batch is cancelled are left alone.
+            channel.send((i, StatusReport::Started)).unwrap();
+            channel
+                .send((i, StatusReport::Cancelled(Vec::new())))
+                .unwrap();
+            continue;
+        }
+        let channel = channel.clone();
+        tasks.push(tokio::spawn(async move {
+            let send = move |report| channel.send((i, report)).unwrap();
+            super::run_split(job.config, job.book, send).await;
+            drop(permit);
+        }));
+    }
+    for task in tasks {
+        task.await.expect("batch task panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::{CancelToken, Output};
+
+    #[test]
+    fn skips_books_of_a_cancelled_batch() {
+        let dir = std::env::temp_dir().join(format!("book_splitter_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let book = dir.join("book.txt");
+        std::fs::write(&book, "Chapter 1\nOne.\nChapter 2\nTwo.\n").unwrap();
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let outputs = [
+            Output::Folder(dir.join("folder")),
+            Output::Zip {
+                file: dir.join("book.zip"),
+                root: String::new(),
+            },
+        ];
+        let jobs = outputs
+            .iter()
+            .map(|output| Job {
+                book: book.clone(),
+                config: SplitConfig {
+                    output: output.clone(),
+                    cancel: cancel.clone(),
+                    ..SplitConfig::new(r"^Chapter \d+$")
+                },
+            })
+            .collect();
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(split_batch(jobs, 1, tx));
+        let reports: Vec<(usize, StatusReport)> = rx.iter().collect();
+        for job in 0..outputs.len() {
+            assert!(reports.iter().any(|(i, report)| *i == job
+                && matches!(report, StatusReport::Cancelled(files) if files.is_empty())));
+        }
+        assert!(!dir.join("folder").exists());
+        assert!(!dir.join("book.zip").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Guessing header regexes for books nobody wrote one for yet.
+//!
+//! Every regex from a fixed list of common header styles is run over the book and scored by
+//! how much its matches look like chapter headers: short lines standing apart from the text,
+//! numbers counting up, a chapter keyword and matches spread over the whole book rather than
+//! bunched up in a table of contents.
+
+use super::numbers::parse_number;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Words that open chapter or part headers, in the languages books usually come in.
+const KEYWORDS: &[&str] = &[
+    "Chapter",
+    "Глава",
+    "Kapitel",
+    "Chapitre",
+    "Capítulo",
+    "Capitolo",
+    "Part",
+    "Часть",
+    "Teil",
+    "Partie",
+    "Book",
+    "Книга",
+];
+
+/// How many candidates [`detect_headers`] returns at most.
+const MAX_CANDIDATES: usize = 8;
+
+/// A header regex that may fit the book.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub pattern: String,
+    /// What kind of headers the pattern matches, for people.
+    pub description: String,
+    /// Number of lines the pattern matches.
+    pub count: usize,
+    /// A few of the matched lines from the beginning, middle and end of the book.
+    pub samples: Vec<String>,
+    /// How likely the matches are chapter headers, higher is better.
+    pub score: f64,
+}
+
+/// Header regexes that match lines of `text` like chapter headers, best first.
+pub fn detect_headers(text: &str) -> Vec<Candidate> {
+    let lines: Vec<&str> = text.lines().collect();
+    let blank_separated = lines.iter().any(|line| line.trim().is_empty());
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut seen = HashSet::new();
+    for (pattern, description, keyword) in patterns() {
+        let Ok(regex) = Regex::new(&pattern) else {
+            continue;
+        };
+        let matches: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line))
+            .map(|(i, _)| i)
+            .collect();
+        // Different patterns often find the same lines; the first, more specific one stays.
+        if matches.len() < 2 || !seen.insert(matches.clone()) {
+            continue;
+        }
+        let score = score(&regex, &lines, &matches, blank_separated, keyword);
+        if score <= 0.0 {
+            continue;
+        }
+        let mut samples: Vec<String> = [0, matches.len() / 2, matches.len() - 1]
+            .iter()
+            .map(|&i| lines[matches[i]].trim().to_owned())
+            .collect();
+        samples.dedup();
+        candidates.push(Candidate {
+            pattern,
+            description,
+            count: matches.len(),
+            samples,
+            score,
+        });
+    }
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Header styles to try, as regexes with descriptions, and whether they start with a keyword.
+fn patterns() -> Vec<(String, String, bool)> {
+    let mut patterns: Vec<(String, String, bool)> = KEYWORDS
+        .iter()
+        .map(|keyword| {
+            (
+                format!(r"^\s*(?i:{keyword})\s+(?P<number>\d+|\p{{L}}+)\b.{{0,60}}$"),
+                format!("“{keyword}” and a number"),
+                true,
+            )
+        })
+        .collect();
+    for (pattern, description) in [
+        (r"^\s*(?P<number>\d{1,3})\.?\s*$", "Number alone on a line"),
+        (
+            r"^\s*(?P<number>[IVXLCDM]+)\.?\s*$",
+            "Roman numeral alone on a line",
+        ),
+        (
+            r"^\s*(?P<number>\d{1,3})[.)]\s+(?P<title>\p{Lu}.{0,60})$",
+            "Number and a title",
+        ),
+        (
+            r"^\s*(?P<number>[IVXLCDM]+)\.\s+(?P<title>\p{Lu}.{0,60})$",
+            "Roman numeral and a title",
+        ),
+        (r"^#{1,3}\s+(?P<title>.+)$", "Markdown heading"),
+        (
+            r"^\s*\p{Lu}[\p{Lu}\d\s,.:;!?'’«»“”—–-]{2,60}$",
+            "Line in capitals",
+        ),
+    ] {
+        patterns.push((pattern.to_owned(), description.to_owned(), false));
+    }
+    patterns
+}
+
+/// Rate how much the `matches` of `regex` among `lines` look like chapter headers.
+fn score(
+    regex: &Regex,
+    lines: &[&str],
+    matches: &[usize],
+    blank_separated: bool,
+    keyword: bool,
+) -> f64 {
+    let count = matches.len() as f64;
+    // Headers are a small share of the lines of a book.
+    if count > lines.len() as f64 / 5.0 {
+        return 0.0;
+    }
+
+    let standing_apart = matches
+        .iter()
+        .filter(|&&i| stands_apart(lines, i, blank_separated))
+        .count() as f64
+        / count;
+
+    let numbers: Vec<usize> = matches
+        .iter()
+        .filter_map(|&i| regex.captures(lines[i])?.name("number"))
+        .filter_map(|number| parse_number(number.as_str()))
+        .collect();
+    let counting = if numbers.len() < 2 {
+        0.0
+    } else {
+        let steps = numbers
+            .windows(2)
+            .filter(|pair| pair[1] == pair[0] + 1 || pair[1] == 1)
+            .count();
+        steps as f64 / (numbers.len() - 1) as f64
+    };
+
+    // A table of contents matches many lines in a row, chapters are far apart.
+    let far_apart = matches
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > 3)
+        .count() as f64
+        / (count - 1.0);
+    let spread = (matches[matches.len() - 1] - matches[0]) as f64 / lines.len() as f64;
+
+    let keyword = if keyword { 2.0 } else { 1.0 };
+    keyword
+        * (0.5 + standing_apart)
+        * (0.5 + counting)
+        * (0.2 + far_apart)
+        * (0.2 + spread)
+        * count.ln_1p()
+}
+
+/// The line is short and set off from the text around it: between blank lines in books that
+/// have them, or else not ending like a sentence.
+fn stands_apart(lines: &[&str], i: usize, blank_separated: bool) -> bool {
+    let line = lines[i].trim();
+    if line.chars().count() > 80 {
+        return false;
+    }
+    if blank_separated {
+        let blank = |i: Option<usize>| {
+            i.and_then(|i| lines.get(i))
+                .is_none_or(|line| line.trim().is_empty())
+        };
+        blank(i.checked_sub(1)) && blank(Some(i + 1))
+    } else {
+        !line.ends_with(['.', ',', ';', '!', '?', '…']) || line.split_whitespace().count() <= 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_the_chapter_headers_first() {
+        let mut text = String::from("The Title\n\nContents\n\nChapter 1\nChapter 2\n\n");
+        for chapter in 1..=12 {
+            text.push_str(&format!("\nChapter {chapter}\n\n"));
+            text.push_str(&format!(
+                "His life began with a letter in chapter {chapter}.\n\
+                 * * *\n\
+                 1. A list item.\n\
+                 2. Another item.\n\
+                 The rest of the chapter goes on for a while.\n\n{}\n\n",
+                chapter * 7
+            ));
+        }
+
+        let candidates = detect_headers(&text);
+        let best = &candidates[0];
+        assert_eq!(best.description, "“Chapter” and a number");
+        let regex = Regex::new(&best.pattern).unwrap();
+        let matched: Vec<&str> = text.lines().filter(|line| regex.is_match(line)).collect();
+        assert_eq!(matched.len(), 14);
+        assert!(matched.iter().all(|line| line.len() <= "Chapter 12".len()));
+        assert_eq!(best.count, 14);
+        assert_eq!(best.samples.last().map(String::as_str), Some("Chapter 12"));
+        assert!(candidates
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+    }
+}
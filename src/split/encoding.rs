@@ -0,0 +1,264 @@
+//! Telling and converting the encodings of plain text books.
+//!
+//! A byte order mark settles the encoding. Without one, UTF-16 is recognized by its zero bytes
+//! and valid UTF-8 is taken as UTF-8; otherwise every single-byte code page in the list is tried
+//! and the one whose text looks most like natural language wins. Chapters can be encoded back
+//! into the encoding of the book.
+
+use encoding_rs::{
+    Encoding, IBM866, KOI8_R, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251, WINDOWS_1252,
+};
+use std::borrow::Cow;
+
+/// Encodings offered to the user when overriding detection.
+pub const SUPPORTED: &[&Encoding] = &[
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    WINDOWS_1251,
+    KOI8_R,
+    IBM866,
+    WINDOWS_1252,
+];
+
+/// Single-byte encodings we try when the input is not valid UTF-8.
+const SINGLE_BYTE: &[&Encoding] = &[WINDOWS_1251, KOI8_R, IBM866, WINDOWS_1252];
+
+/// How many bytes are enough to guess the encoding.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Most frequent lowercase letters of Russian text.
+const FREQUENT_CYRILLIC: &str = "оеаинтсрвл";
+
+/// Guess the encoding of `bytes` from its BOM or, failing that, byte statistics.
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    let sample = &bytes[..bytes.len().min(SAMPLE_SIZE)];
+    if let Some(encoding) = detect_utf16(sample) {
+        return encoding;
+    }
+    if is_utf8(sample, sample.len() < bytes.len()) {
+        return UTF_8;
+    }
+
+    SINGLE_BYTE
+        .iter()
+        .copied()
+        .max_by_key(|encoding| score(&encoding.decode_without_bom_handling(sample).0))
+        .unwrap_or(WINDOWS_1252)
+}
+
+/// Decode `bytes` to UTF-8, skipping the BOM if there is one.
+pub fn decode<'a>(bytes: &'a [u8], encoding: &'static Encoding) -> Cow<'a, str> {
+    match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_len)) if bom_encoding == encoding => {
+            encoding.decode_without_bom_handling(&bytes[bom_len..]).0
+        }
+        _ => encoding.decode_without_bom_handling(bytes).0,
+    }
+}
+
+/// `bytes` start with the BOM of `encoding`, which [`decode`] skips.
+pub fn has_bom(bytes: &[u8], encoding: &'static Encoding) -> bool {
+    Encoding::for_bom(bytes).is_some_and(|(bom_encoding, _)| bom_encoding == encoding)
+}
+
+/// Encode `text` back into `encoding`. UTF-16 output gets a BOM so that readers can recognize it.
+pub fn encode<'a>(text: &'a str, encoding: &'static Encoding) -> Cow<'a, [u8]> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return Cow::Owned(encode_with_bom(text, encoding, true));
+    }
+    encoding.encode(text).0
+}
+
+/// Encode `text` into `encoding`, with a BOM in front if `bom` is set and the encoding has one.
+pub fn encode_with_bom(text: &str, encoding: &'static Encoding, bom: bool) -> Vec<u8> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        let bom = bom.then_some(0xFEFF);
+        for unit in bom.into_iter().chain(text.encode_utf16()) {
+            if encoding == UTF_16LE {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return bytes;
+    }
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    if bom && encoding == UTF_8 {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    bytes.extend_from_slice(&encoding.encode(text).0);
+    bytes
+}
+
+/// Number of bytes `text` takes in `encoding`, without a BOM.
+pub fn encoded_len(text: &str, encoding: &'static Encoding) -> usize {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        text.encode_utf16().count() * 2
+    } else if encoding == UTF_8 {
+        text.len()
+    } else {
+        encoding.encode(text).0.len()
+    }
+}
+
+/// UTF-16 without a BOM has every other byte set to a tiny value: `0x00` for Latin text,
+/// `0x04` for Cyrillic and so on. Plain text almost never contains such control bytes.
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let is_high_byte = |b: &&u8| **b < 0x09;
+    let even_low = sample.iter().step_by(2).filter(is_high_byte).count();
+    let odd_low = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(is_high_byte)
+        .count();
+    if odd_low * 10 > pairs * 3 && even_low * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_low * 10 > pairs * 3 && odd_low * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Check for valid UTF-8, allowing a character cut in half at the end of a truncated sample.
+fn is_utf8(sample: &[u8], truncated: bool) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => truncated && e.error_len().is_none(),
+    }
+}
+
+/// Rate how much `text` looks like natural language.
+///
+/// Cyrillic words are made of Cyrillic letters only and are mostly lowercase, while accented
+/// Latin letters sit between plain ASCII ones. Text decoded with the wrong code page breaks
+/// both rules.
+fn score(text: &str) -> i64 {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0i64;
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii() {
+            continue;
+        }
+        let prev = if i > 0 { chars[i - 1] } else { ' ' };
+        let next = chars.get(i + 1).copied().unwrap_or(' ');
+        let near_ascii_letter = prev.is_ascii_alphabetic() || next.is_ascii_alphabetic();
+
+        score += if is_cyrillic(c) {
+            if near_ascii_letter {
+                -1
+            } else if c.is_lowercase() {
+                if FREQUENT_CYRILLIC.contains(c) {
+                    2
+                } else {
+                    1
+                }
+            } else if prev.is_lowercase() {
+                -1
+            } else {
+                0
+            }
+        } else if c.is_alphabetic() {
+            if near_ascii_letter {
+                1
+            } else {
+                0
+            }
+        } else if c.is_control() || c == '\u{FFFD}' {
+            -2
+        } else {
+            0
+        };
+    }
+    score
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUSSIAN: &str = "Глава первая. Он вошёл в комнату и сел у окна, где было светло и тихо.";
+
+    #[test]
+    fn detects_boms() {
+        assert_eq!(detect(b"\xEF\xBB\xBFtext"), UTF_8);
+        assert_eq!(detect(b"\xFF\xFEt\x00"), UTF_16LE);
+        assert_eq!(detect(b"\xFE\xFF\x00t"), UTF_16BE);
+        assert_eq!(decode(b"\xEF\xBB\xBFtext", UTF_8), "text");
+        assert!(has_bom(b"\xEF\xBB\xBFtext", UTF_8));
+        assert!(!has_bom(b"text", UTF_8));
+    }
+
+    #[test]
+    fn detects_utf8() {
+        assert_eq!(detect(b"plain ASCII text"), UTF_8);
+        assert_eq!(detect(RUSSIAN.as_bytes()), UTF_8);
+        assert_eq!(detect("Café déjà vu".as_bytes()), UTF_8);
+    }
+
+    #[test]
+    fn detects_utf16_without_bom() {
+        let le: Vec<u8> = RUSSIAN.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = RUSSIAN.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(detect(&le), UTF_16LE);
+        assert_eq!(detect(&be), UTF_16BE);
+        assert_eq!(decode(&le, UTF_16LE), RUSSIAN);
+    }
+
+    #[test]
+    fn detects_cyrillic_code_pages() {
+        for encoding in [WINDOWS_1251, KOI8_R, IBM866] {
+            let bytes = encoding.encode(RUSSIAN).0;
+            assert_eq!(detect(&bytes), encoding, "{}", encoding.name());
+            assert_eq!(decode(&bytes, encoding), RUSSIAN);
+        }
+    }
+
+    #[test]
+    fn detects_western_text() {
+        let bytes = WINDOWS_1252
+            .encode("Le café était déjà fermé à l'heure où")
+            .0;
+        assert_eq!(detect(&bytes), WINDOWS_1252);
+    }
+
+    #[test]
+    fn misdetects_text_too_short_to_tell() {
+        // With no ASCII letters around it, a lone `É` is the byte KOI8-R uses for `и`, one of
+        // the most frequent Russian letters.
+        let bytes = WINDOWS_1252.encode("É").0;
+        assert_eq!(detect(&bytes), KOI8_R);
+        assert_eq!(decode(&bytes, detect(&bytes)), "и");
+    }
+
+    #[test]
+    fn encodes_back_with_or_without_bom() {
+        assert_eq!(encode_with_bom("t", UTF_8, true), b"\xEF\xBB\xBFt");
+        assert_eq!(encode_with_bom("t", UTF_16LE, true), b"\xFF\xFEt\x00");
+        assert_eq!(encode_with_bom("t", UTF_16BE, false), b"\x00t");
+        assert_eq!(encoded_len("ая", UTF_16BE), 4);
+        assert_eq!(encoded_len("ая", WINDOWS_1251), 2);
+    }
+
+    #[test]
+    fn encodes_utf16_with_bom() {
+        assert_eq!(encode("t", UTF_16LE).as_ref(), b"\xFF\xFEt\x00");
+        assert_eq!(encode("t", UTF_16BE).as_ref(), b"\xFE\xFF\x00t");
+        assert_eq!(encode("ая", WINDOWS_1251).as_ref(), b"\xE0\xFF");
+    }
+}
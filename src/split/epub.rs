@@ -0,0 +1,761 @@
+//! Reading EPUB books and writing chapters as one.
+//!
+//! The text of a book is taken from its spine in reading order, a line per paragraph, and its
+//! table of contents from the EPUB 3 navigation document or the older NCX. Chapters are written
+//! as an EPUB 3 book with both, so that old readers find their way too.
+
+use super::xml::{element_text, escape, nest, parse_xml, utc_now, TextWriter};
+use super::{Book, Chapter, Metadata, SplitError, TocEntry};
+use encoding_rs::UTF_8;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Collects chapters and packs them into an EPUB 3 book, with an NCX table of contents for
+/// older readers.
+pub struct EpubBuilder<'a> {
+    metadata: &'a Metadata,
+    /// [`Book::sha256`] of the book the chapters come from.
+    sha256: &'a str,
+    /// Title and XHTML body of every chapter.
+    chapters: Vec<(usize, String, String)>,
+}
+
+impl<'a> EpubBuilder<'a> {
+    pub fn new(metadata: &'a Metadata, sha256: &'a str) -> Self {
+        Self {
+            metadata,
+            sha256,
+            chapters: Vec::new(),
+        }
+    }
+
+    /// Add a chapter. The header line, if any, becomes its heading and the chapter title goes
+    /// to the table of contents, nested under the headers the chapter lies under; every other
+    /// non-blank line becomes a paragraph.
+    pub fn add_chapter(&mut self, chapter: &Chapter<'_>) {
+        let depth = chapter.parents.len();
+        let mut body = String::new();
+        let mut lines = chapter.lines.iter();
+        if let Some(header) = chapter.header {
+            lines.next();
+            let heading = (depth + 1).min(6);
+            body.push_str(&format!(
+                "<h{heading}>{}</h{heading}>\n",
+                escape(header.trim())
+            ));
+        }
+        let title = match chapter.title {
+            Some(title) => title.trim().to_owned(),
+            None if self.metadata.title.is_empty() => "Start".to_owned(),
+            None => self.metadata.title.clone(),
+        };
+        for line in lines
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+        {
+            body.push_str(&format!("<p>{}</p>\n", escape(line)));
+        }
+        self.chapters.push((depth, title, body));
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, SplitError> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // The mimetype has to come first and uncompressed.
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER.as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package().as_bytes())?;
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav().as_bytes())?;
+        zip.start_file("OEBPS/toc.ncx", deflated)?;
+        zip.write_all(self.ncx().as_bytes())?;
+        for (i, (_, title, body)) in self.chapters.iter().enumerate() {
+            zip.start_file(format!("OEBPS/{}", chapter_file(i)), deflated)?;
+            zip.write_all(self.chapter(title, body).as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn language(&self) -> &str {
+        if self.metadata.language.is_empty() {
+            "en"
+        } else {
+            &self.metadata.language
+        }
+    }
+
+    /// An identifier derived from the hash of the book, so re-splitting it gives the same one.
+    fn identifier(&self) -> String {
+        format!("urn:book-splitter:{}", self.sha256)
+    }
+
+    fn package(&self) -> String {
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for i in 0..self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"c{i}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                chapter_file(i)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"c{i}\"/>\n"));
+        }
+        let creator = if self.metadata.author.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "    <dc:creator>{}</dc:creator>\n",
+                escape(&self.metadata.author)
+            )
+        };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+{creator}    <dc:language>{language}</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#,
+            identifier = self.identifier(),
+            title = escape(&self.book_title()),
+            language = escape(self.language()),
+            modified = utc_now(),
+        )
+    }
+
+    fn book_title(&self) -> String {
+        if self.metadata.title.is_empty() {
+            "Untitled".to_owned()
+        } else {
+            self.metadata.title.clone()
+        }
+    }
+
+    fn nav(&self) -> String {
+        let items = nest(
+            self.chapters
+                .iter()
+                .enumerate()
+                .map(|(i, (depth, title, _))| {
+                    let link = format!(
+                        "      <li><a href=\"{}\">{}</a>",
+                        chapter_file(i),
+                        escape(title)
+                    );
+                    (*depth, link)
+                }),
+            "\n<ol>\n",
+            "</ol>",
+            "</li>\n",
+        );
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{language}">
+<head><title>{title}</title></head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <ol>
+{items}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+            language = escape(self.language()),
+            title = escape(&self.book_title()),
+        )
+    }
+
+    fn ncx(&self) -> String {
+        let points = nest(
+            self.chapters.iter().enumerate().map(|(i, (depth, title, _))| {
+                let point = format!(
+                    "    <navPoint id=\"p{i}\" playOrder=\"{}\"><navLabel><text>{}</text></navLabel><content src=\"{}\"/>",
+                    i + 1,
+                    escape(title),
+                    chapter_file(i)
+                );
+                (*depth, point)
+            }),
+            "\n",
+            "",
+            "</navPoint>\n",
+        );
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head><meta name="dtb:uid" content="{identifier}"/></head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>
+{points}  </navMap>
+</ncx>
+"#,
+            identifier = self.identifier(),
+            title = escape(&self.book_title()),
+        )
+    }
+
+    fn chapter(&self, title: &str, body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" lang="{language}">
+<head><title>{title}</title></head>
+<body>
+{body}</body>
+</html>
+"#,
+            language = escape(self.language()),
+            title = escape(title),
+        )
+    }
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:04}.xhtml", index + 1)
+}
+
+/// Whether `bytes` look like an EPUB: a ZIP whose first entry is the EPUB mimetype.
+pub fn is_epub(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"PK\x03\x04") && bytes.get(30..58) == Some(b"mimetypeapplication/epub+zip")
+}
+
+/// Extract the text of an EPUB in reading order, a line per paragraph, along with its table of
+/// contents.
+pub fn read_epub(bytes: &[u8]) -> Result<Book, SplitError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let container = parse_xml(&container)?;
+    let package_path = container
+        .descendants()
+        .find(|n| n.has_tag_name("rootfile"))
+        .and_then(|n| n.attribute("full-path"))
+        .ok_or_else(|| SplitError::Malformed("EPUB has no package document".to_owned()))?
+        .to_owned();
+    let package_dir = parent_dir(&package_path);
+
+    let package = read_entry(&mut archive, &package_path)?;
+    let package = parse_xml(&package)?;
+    // Manifest id -> (path, properties, media type)
+    let manifest: HashMap<&str, (String, &str, &str)> = package
+        .descendants()
+        .filter(|n| n.has_tag_name("item"))
+        .filter_map(|n| {
+            let path = resolve(package_dir, n.attribute("href")?);
+            Some((
+                n.attribute("id")?,
+                (
+                    path,
+                    n.attribute("properties").unwrap_or_default(),
+                    n.attribute("media-type").unwrap_or_default(),
+                ),
+            ))
+        })
+        .collect();
+    let spine = package.descendants().find(|n| n.has_tag_name("spine"));
+
+    let mut text = TextWriter::default();
+    // Line of every document start and every element id, keyed by `path` and `path#id`.
+    let mut anchors = HashMap::new();
+    for idref in spine
+        .iter()
+        .flat_map(|spine| spine.children())
+        .filter(|n| n.has_tag_name("itemref"))
+        .filter_map(|n| n.attribute("idref"))
+    {
+        let Some((path, _, _)) = manifest.get(idref) else {
+            continue;
+        };
+        let document = read_entry(&mut archive, path)?;
+        let document = parse_xml(&document)?;
+        text.break_line();
+        anchors.insert(path.clone(), text.lines());
+        write_node(&mut text, document.root(), path, &mut anchors);
+        text.break_line();
+    }
+
+    // Prefer the EPUB 3 navigation document, fall back to the NCX of EPUB 2.
+    let mut toc_links = Vec::new();
+    let nav = manifest
+        .values()
+        .find(|(_, properties, _)| properties.split_whitespace().any(|p| p == "nav"));
+    let ncx = spine
+        .and_then(|spine| spine.attribute("toc"))
+        .and_then(|id| manifest.get(id))
+        .or_else(|| {
+            manifest
+                .values()
+                .find(|(_, _, media_type)| *media_type == "application/x-dtbncx+xml")
+        });
+    if let Some((path, _, _)) = nav {
+        let document = read_entry(&mut archive, path)?;
+        let document = parse_xml(&document)?;
+        let toc = document
+            .descendants()
+            .filter(|n| n.has_tag_name("nav"))
+            .find(|n| n.attribute((OPS_NS, "type")) == Some("toc"))
+            .or_else(|| document.descendants().find(|n| n.has_tag_name("nav")));
+        if let Some(toc) = toc {
+            nav_links(toc, parent_dir(path), 0, &mut toc_links);
+        }
+    } else if let Some((path, _, _)) = ncx {
+        let document = read_entry(&mut archive, path)?;
+        let document = parse_xml(&document)?;
+        if let Some(nav_map) = document.descendants().find(|n| n.has_tag_name("navMap")) {
+            ncx_links(nav_map, parent_dir(path), 0, &mut toc_links);
+        }
+    }
+
+    let toc = toc_links
+        .into_iter()
+        .filter_map(|(target, title, depth)| {
+            let line = anchors.get(&target).or_else(|| {
+                // Unknown fragment: fall back to the start of the document.
+                anchors.get(target.split('#').next().unwrap_or_default())
+            })?;
+            Some(TocEntry {
+                line: (*line).min(text.lines().saturating_sub(1)),
+                title,
+                depth,
+            })
+        })
+        .collect();
+
+    Ok(Book {
+        text: text.into_text(),
+        encoding: UTF_8,
+        toc,
+        metadata: Metadata::default(),
+        path: None,
+        sha256: String::new(),
+        bom: false,
+    })
+}
+
+const OPS_NS: &str = "http://www.idpf.org/2007/ops";
+
+/// Elements that start a new line of text.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "dt",
+    "dd",
+    "blockquote",
+    "pre",
+    "section",
+    "article",
+    "aside",
+    "header",
+    "footer",
+    "tr",
+    "table",
+    "ul",
+    "ol",
+    "dl",
+    "hr",
+    "figure",
+    "figcaption",
+    "body",
+];
+
+/// Elements whose content is not part of the text.
+const SKIPPED: &[&str] = &["head", "script", "style"];
+
+/// HTML entities that show up in EPUBs although XHTML does not define them.
+const HTML_ENTITIES: &[(&str, &str)] = &[
+    ("&nbsp;", "&#160;"),
+    ("&shy;", "&#173;"),
+    ("&ndash;", "&#8211;"),
+    ("&mdash;", "&#8212;"),
+    ("&lsquo;", "&#8216;"),
+    ("&rsquo;", "&#8217;"),
+    ("&ldquo;", "&#8220;"),
+    ("&rdquo;", "&#8221;"),
+    ("&laquo;", "&#171;"),
+    ("&raquo;", "&#187;"),
+    ("&hellip;", "&#8230;"),
+    ("&copy;", "&#169;"),
+];
+
+/// Write the text of an XHTML `node`, recording the line of every element id in `anchors`.
+fn write_node(
+    text: &mut TextWriter,
+    node: roxmltree::Node<'_, '_>,
+    path: &str,
+    anchors: &mut HashMap<String, usize>,
+) {
+    for child in node.children() {
+        if child.is_text() {
+            text.write_text(child.text().unwrap_or_default());
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+
+        let name = child.tag_name().name();
+        if SKIPPED.contains(&name) {
+            continue;
+        }
+        if let Some(id) = child.attribute("id") {
+            anchors.insert(format!("{path}#{id}"), text.lines());
+        }
+        if name == "br" {
+            text.break_line();
+        } else if BLOCKS.contains(&name) {
+            text.break_line();
+            write_node(text, child, path, anchors);
+            text.break_line();
+        } else {
+            write_node(text, child, path, anchors);
+        }
+    }
+}
+
+/// Collect `(target, title, depth)` from the nested lists of an EPUB 3 `nav`.
+fn nav_links(
+    node: roxmltree::Node<'_, '_>,
+    dir: &str,
+    depth: usize,
+    links: &mut Vec<(String, String, usize)>,
+) {
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "ol" => nav_links(child, dir, depth + 1, links),
+            "li" => {
+                if let Some(link) = child.children().find(|n| n.has_tag_name("a")) {
+                    if let Some(href) = link.attribute("href") {
+                        let title = element_text(link);
+                        links.push((resolve(dir, href), title, depth.saturating_sub(1)));
+                    }
+                }
+                nav_links(child, dir, depth, links);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Collect `(target, title, depth)` from the nested `navPoint`s of an NCX.
+fn ncx_links(
+    node: roxmltree::Node<'_, '_>,
+    dir: &str,
+    depth: usize,
+    links: &mut Vec<(String, String, usize)>,
+) {
+    for point in node.children().filter(|n| n.has_tag_name("navPoint")) {
+        let title = point
+            .children()
+            .find(|n| n.has_tag_name("navLabel"))
+            .map(element_text)
+            .unwrap_or_default();
+        let src = point
+            .children()
+            .find(|n| n.has_tag_name("content"))
+            .and_then(|n| n.attribute("src"));
+        if let Some(src) = src {
+            links.push((resolve(dir, src), title, depth));
+        }
+        ncx_links(point, dir, depth + 1, links);
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<String, SplitError> {
+    let mut entry = archive.by_name(path)?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)?;
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    for (entity, reference) in HTML_ENTITIES {
+        if text.contains(entity) {
+            text = text.replace(entity, reference);
+        }
+    }
+    Ok(text)
+}
+
+/// Directory part of a path inside the archive, with a trailing `/` unless empty.
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..=i])
+}
+
+/// Resolve `href` relative to `dir`, percent-decoding it and normalizing `.` and `..`.
+fn resolve(dir: &str, href: &str) -> String {
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let mut parts: Vec<String> = Vec::new();
+    let joined = format!("{dir}{}", percent_decode(path));
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part.to_owned()),
+        }
+    }
+    let path = parts.join("/");
+    match fragment {
+        Some(fragment) => format!("{path}#{fragment}"),
+        None => path,
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::tests::chapter;
+    use crate::split::{toc_chapters, Division, Numbering};
+
+    /// An EPUB holding `files`, after the mimetype and a container pointing to `package`.
+    fn epub(package: &str, files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("mimetype", stored).unwrap();
+        zip.write_all(b"application/epub+zip").unwrap();
+        zip.start_file("META-INF/container.xml", stored).unwrap();
+        zip.write_all(CONTAINER.replace("OEBPS/content.opf", package).as_bytes())
+            .unwrap();
+        for (name, contents) in files {
+            zip.start_file(*name, stored).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// A book of three chapters for the book with hash `sha256`.
+    fn written(sha256: &str) -> Vec<u8> {
+        let metadata = Metadata::default();
+        let mut builder = EpubBuilder::new(&metadata, sha256);
+        for number in 1..=3 {
+            let header = format!("Chapter {number}");
+            builder.add_chapter(&chapter(number, &[&header, "Text."]));
+        }
+        builder.finish().unwrap()
+    }
+
+    fn entry(bytes: &[u8], name: &str) -> String {
+        read_entry(&mut ZipArchive::new(Cursor::new(bytes)).unwrap(), name).unwrap()
+    }
+
+    #[test]
+    fn stores_the_mimetype_first() {
+        let bytes = written("0123");
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice())).unwrap();
+        let mut mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        let mut contents = String::new();
+        mimetype.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "application/epub+zip");
+    }
+
+    #[test]
+    fn identifies_the_book_by_its_hash() {
+        let identifier = |bytes: &[u8]| {
+            let package = entry(bytes, "OEBPS/content.opf");
+            let package = parse_xml(&package).unwrap();
+            let identifier = package
+                .descendants()
+                .find(|n| n.has_tag_name("identifier"))
+                .map(element_text);
+            identifier.unwrap()
+        };
+        assert_eq!(identifier(&written("0123")), "urn:book-splitter:0123");
+        assert_eq!(identifier(&written("0123")), identifier(&written("0123")));
+        assert_ne!(identifier(&written("0123")), identifier(&written("4567")));
+        assert!(entry(&written("0123"), "OEBPS/toc.ncx").contains("urn:book-splitter:0123"));
+    }
+
+    #[test]
+    fn lists_every_chapter_in_both_tables_of_contents() {
+        let bytes = written("0123");
+        for toc in ["OEBPS/nav.xhtml", "OEBPS/toc.ncx"] {
+            let document = entry(&bytes, toc);
+            for number in 1..=3 {
+                let file = chapter_file(number - 1);
+                assert_eq!(document.matches(&file).count(), 1, "{toc} {file}");
+                assert!(document.contains(&format!("Chapter {number}")));
+            }
+        }
+        let package = entry(&bytes, "OEBPS/content.opf");
+        assert_eq!(package.matches("<itemref ").count(), 3);
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let metadata = Metadata::default();
+        let mut builder = EpubBuilder::new(&metadata, "0123");
+        let part = Division {
+            level: 0,
+            number: 1,
+            title: "Part One",
+        };
+        builder.add_chapter(&chapter(1, &["Part One", "Foreword."]));
+        for (number, lines) in [
+            (1, ["Chapter 1", "One."]),
+            (2, ["Chapter 2", "Two & more."]),
+        ] {
+            builder.add_chapter(&Chapter {
+                level: 1,
+                parents: vec![part],
+                ..chapter(number, &lines)
+            });
+        }
+        let bytes = builder.finish().unwrap();
+        assert!(is_epub(&bytes));
+
+        let book = read_epub(&bytes).unwrap();
+        let text: Vec<&str> = book.text.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(
+            text,
+            [
+                "Part One",
+                "Foreword.",
+                "Chapter 1",
+                "One.",
+                "Chapter 2",
+                "Two & more."
+            ]
+        );
+        let toc: Vec<(&str, usize)> = book
+            .toc
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.depth))
+            .collect();
+        assert_eq!(toc, [("Part One", 0), ("Chapter 1", 1), ("Chapter 2", 1)]);
+
+        let lines: Vec<(usize, &str)> = book.text.lines().enumerate().collect();
+        let chapters = toc_chapters(&book.toc, 2, Numbering::Continuous, &lines, 1);
+        let headers: Vec<Option<&str>> = chapters.iter().map(|c| c.header).collect();
+        assert_eq!(
+            headers,
+            [Some("Part One"), Some("Chapter 1"), Some("Chapter 2")]
+        );
+        assert_eq!(chapters[2].parents[0].title, "Part One");
+        assert!(chapters[2].text().contains("Two & more."));
+    }
+
+    #[test]
+    fn reads_the_ncx_without_a_nav_document() {
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <manifest>
+    <item id="toc" href="toc/toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="c1" href="Text/ch%201.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="toc"><itemref idref="c1"/></spine>
+</package>"#;
+        let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/"><navMap>
+  <navPoint><navLabel><text>One</text></navLabel><content src="../Text/ch%201.xhtml"/>
+    <navPoint><navLabel><text>Two</text></navLabel><content src="../Text/ch%201.xhtml#two"/></navPoint>
+  </navPoint>
+  <navPoint><navLabel><text>Lost</text></navLabel><content src="../Text/ch%201.xhtml#nowhere"/></navPoint>
+</navMap></ncx>"#;
+        let chapter = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Skipped</title></head>
+<body><h1>One</h1><p>First&nbsp;line.</p><h2 id="two">Two</h2><p>Second line.</p></body></html>"#;
+        let bytes = epub(
+            "OEBPS/content.opf",
+            &[
+                ("OEBPS/content.opf", package),
+                ("OEBPS/toc/toc.ncx", ncx),
+                ("OEBPS/Text/ch 1.xhtml", chapter),
+            ],
+        );
+
+        let book = read_epub(&bytes).unwrap();
+        let lines: Vec<&str> = book.text.lines().collect();
+        let toc: Vec<(&str, &str, usize)> = book
+            .toc
+            .iter()
+            .map(|entry| (entry.title.as_str(), lines[entry.line], entry.depth))
+            .collect();
+        // An unknown fragment points to the start of its document.
+        assert_eq!(
+            toc,
+            [("One", "One", 0), ("Two", "Two", 1), ("Lost", "One", 0)]
+        );
+        // `&nbsp;` is not XML, and white space all the same.
+        assert!(book.text.contains("First line."));
+        assert!(!book.text.contains("Skipped"));
+    }
+
+    #[test]
+    fn resolves_hrefs() {
+        assert_eq!(resolve("OEBPS/", "Text/ch1.xhtml"), "OEBPS/Text/ch1.xhtml");
+        assert_eq!(
+            resolve("OEBPS/toc/", "../Text/ch%201.xhtml#p2"),
+            "OEBPS/Text/ch 1.xhtml#p2"
+        );
+        assert_eq!(resolve("", "./a/../b.xhtml"), "b.xhtml");
+        assert_eq!(parent_dir("OEBPS/content.opf"), "OEBPS/");
+        assert_eq!(parent_dir("content.opf"), "");
+    }
+
+    #[test]
+    fn percent_decodes_utf8_and_leaves_bad_escapes() {
+        assert_eq!(
+            percent_decode("%D0%93%D0%BB%D0%B0%D0%B2%D0%B0%201"),
+            "Глава 1"
+        );
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
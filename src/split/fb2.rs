@@ -0,0 +1,377 @@
+//! Reading FictionBook (FB2) books, plain or zipped, and writing chapters as one.
+//!
+//! The text of a book is a line per paragraph or verse, and its nested sections make up its
+//! table of contents. Chapters are written as sections of a single FB2 document.
+
+use super::xml::{element_text, escape, nest, parse_xml, utc_now, TextWriter};
+use super::{encoding, Book, Chapter, Metadata, SplitError, TocEntry};
+use encoding_rs::{Encoding, UTF_8};
+
+/// Whether `bytes` look like a FictionBook document.
+pub fn is_fb2(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(1024)]
+        .windows(12)
+        .any(|w| w == b"<FictionBook")
+}
+
+/// Extract the text of an FB2 document, a line per paragraph or verse, with its section tree
+/// as the table of contents.
+pub fn read_fb2(bytes: &[u8]) -> Result<Book, SplitError> {
+    let encoding = declared_encoding(bytes);
+    let source = encoding::decode(bytes, encoding);
+    let document = parse_xml(&source)?;
+
+    let mut text = TextWriter::default();
+    let mut toc = Vec::new();
+    for body in document
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("body"))
+    {
+        text.break_line();
+        // Extra bodies hold notes and comments.
+        if let Some(name) = body.attribute("name") {
+            toc.push(TocEntry {
+                line: text.lines(),
+                title: title_text(body).unwrap_or_else(|| name.to_owned()),
+                depth: 0,
+            });
+        }
+        write_blocks(&mut text, body, 0, &mut toc);
+    }
+
+    let text = text.into_text();
+    // Untitled sections are named after their first line.
+    let lines: Vec<&str> = text.lines().collect();
+    for entry in &mut toc {
+        if entry.title.is_empty() {
+            entry.title = lines
+                .get(entry.line)
+                .copied()
+                .unwrap_or_default()
+                .to_owned();
+        }
+    }
+
+    Ok(Book {
+        text,
+        encoding,
+        toc,
+        metadata: Metadata::default(),
+        path: None,
+        sha256: String::new(),
+        bom: false,
+    })
+}
+
+/// The encoding from the XML declaration, UTF-8 if there is none.
+fn declared_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(200)]);
+    let Some(declaration) = head.strip_prefix("<?xml") else {
+        return UTF_8;
+    };
+    let declaration = &declaration[..declaration.find("?>").unwrap_or(declaration.len())];
+    declaration
+        .split_once("encoding=")
+        .and_then(|(_, rest)| {
+            let quote = rest.chars().next()?;
+            rest[1..].split(quote).next()
+        })
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8)
+}
+
+/// Write the block content of a body, section or other container. Sections become table of
+/// contents entries at `depth`.
+fn write_blocks(
+    text: &mut TextWriter,
+    node: roxmltree::Node<'_, '_>,
+    depth: usize,
+    toc: &mut Vec<TocEntry>,
+) {
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "section" => {
+                text.break_line();
+                toc.push(TocEntry {
+                    line: text.lines(),
+                    title: title_text(child).unwrap_or_default(),
+                    depth,
+                });
+                write_blocks(text, child, depth + 1, toc);
+            }
+            // A title is one line, whatever number of paragraphs it has.
+            "title" => {
+                text.break_line();
+                for paragraph in child.children().filter(|n| n.is_element()) {
+                    write_inline(text, paragraph);
+                    text.write_text(" ");
+                }
+                text.break_line();
+            }
+            "p" | "v" | "subtitle" | "text-author" | "date" | "td" | "th" => {
+                text.break_line();
+                write_inline(text, child);
+                text.break_line();
+            }
+            "poem" | "stanza" | "epigraph" | "cite" | "annotation" | "table" | "tr" => {
+                write_blocks(text, child, depth, toc);
+            }
+            "image" | "empty-line" | "binary" => {}
+            _ => write_inline(text, child),
+        }
+    }
+}
+
+fn write_inline(text: &mut TextWriter, node: roxmltree::Node<'_, '_>) {
+    for child in node.descendants().filter(|n| n.is_text()) {
+        text.write_text(child.text().unwrap_or_default());
+    }
+}
+
+/// Paragraphs of the `title` of `node` joined into one line.
+fn title_text(node: roxmltree::Node<'_, '_>) -> Option<String> {
+    let title = node.children().find(|n| n.has_tag_name("title"))?;
+    let paragraphs: Vec<String> = title
+        .children()
+        .filter(|n| n.is_element())
+        .map(element_text)
+        .filter(|p| !p.is_empty())
+        .collect();
+    Some(paragraphs.join(" ")).filter(|title| !title.is_empty())
+}
+
+/// Collects chapters and writes them as sections of one FB2 document.
+pub struct Fb2Builder<'a> {
+    metadata: &'a Metadata,
+    /// Depth and the opening of the `<section>` of every chapter, up to its subsections.
+    sections: Vec<(usize, String)>,
+}
+
+impl<'a> Fb2Builder<'a> {
+    pub fn new(metadata: &'a Metadata) -> Self {
+        Self {
+            metadata,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Add a chapter. The header line, if any, becomes the section title; every other non-blank
+    /// line becomes a paragraph. Chapters go into the sections of the headers they lie under.
+    pub fn add_chapter(&mut self, chapter: &Chapter<'_>) {
+        let mut section = String::from("<section>\n");
+        let mut lines = chapter.lines.iter();
+        if let Some(header) = chapter.header {
+            lines.next();
+            section.push_str(&format!(
+                "<title><p>{}</p></title>\n",
+                escape(header.trim())
+            ));
+        }
+        for line in lines
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+        {
+            section.push_str(&format!("<p>{}</p>\n", escape(line)));
+        }
+        self.sections.push((chapter.parents.len(), section));
+    }
+
+    /// The whole book with the title from the metadata.
+    pub fn finish(self) -> String {
+        let title = if self.metadata.title.is_empty() {
+            "Untitled"
+        } else {
+            &self.metadata.title
+        };
+        self.document(title, "")
+    }
+
+    /// A book of its own for one chapter, titled after it and numbered in a sequence named
+    /// after the whole book.
+    pub fn chapter_document(metadata: &'a Metadata, chapter: &Chapter<'_>) -> String {
+        let mut builder = Self::new(metadata);
+        builder.add_chapter(chapter);
+        let number = chapter.number;
+        let title = chapter
+            .title
+            .map(|title| title.trim().to_owned())
+            .unwrap_or_else(|| number.to_string());
+        let sequence = if metadata.title.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<sequence name=\"{}\" number=\"{number}\"/>",
+                escape(&metadata.title)
+            )
+        };
+        builder.document(&title, &sequence)
+    }
+
+    fn document(&self, title: &str, sequence: &str) -> String {
+        let language = if self.metadata.language.is_empty() {
+            "en"
+        } else {
+            &self.metadata.language
+        };
+        let now = utc_now();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+<description>
+<title-info>
+<genre>prose</genre>
+{author}
+<book-title>{title}</book-title>
+<lang>{language}</lang>
+{sequence}
+</title-info>
+<document-info>
+<author><nickname>Book Splitter</nickname></author>
+<program-used>Book Splitter</program-used>
+<date value="{date}">{date}</date>
+<id>book-splitter-{id}</id>
+<version>1.0</version>
+</document-info>
+</description>
+<body>
+{sections}</body>
+</FictionBook>
+"#,
+            author = author(&self.metadata.author),
+            title = escape(title),
+            language = escape(language),
+            date = &now[..10],
+            id = now.replace([':', '-'], ""),
+            sections = nest(self.sections.iter().cloned(), "", "", "</section>\n"),
+        )
+    }
+}
+
+/// FB2 wants the parts of the author name separately.
+fn author(name: &str) -> String {
+    let parts: Vec<String> = name.split_whitespace().map(escape).collect();
+    match parts.as_slice() {
+        [] => "<author><nickname>Unknown</nickname></author>".to_owned(),
+        [nickname] => format!("<author><nickname>{nickname}</nickname></author>"),
+        [first, last] => {
+            format!("<author><first-name>{first}</first-name><last-name>{last}</last-name></author>")
+        }
+        [first, middle @ .., last] => format!(
+            "<author><first-name>{first}</first-name><middle-name>{}</middle-name><last-name>{last}</last-name></author>",
+            middle.join(" ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::tests::chapter;
+    use crate::split::{decode_book, Division};
+    use encoding_rs::WINDOWS_1251;
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+    use zip::ZipWriter;
+
+    const NESTED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0">
+<body>
+<title><p>The Book</p></title>
+<section><title><p>Part One</p></title>
+  <section><title><p>Chapter 1</p><p>Beginnings</p></title><p>One.</p></section>
+  <section><p>Untitled start.</p><poem><stanza><v>A verse.</v></stanza></poem></section>
+</section>
+<section><title><p>Part Two</p></title><p>Two.</p></section>
+</body>
+<body name="notes"><section><title><p>1</p></title><p>A note.</p></section></body>
+</FictionBook>
+"#;
+
+    fn toc(book: &Book) -> Vec<(&str, usize)> {
+        book.toc
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.depth))
+            .collect()
+    }
+
+    #[test]
+    fn reads_nested_sections_as_the_table_of_contents() {
+        let book = read_fb2(NESTED.as_bytes()).unwrap();
+        assert_eq!(
+            toc(&book),
+            [
+                ("Part One", 0),
+                ("Chapter 1 Beginnings", 1),
+                ("Untitled start.", 1),
+                ("Part Two", 0),
+                ("notes", 0),
+                ("1", 0),
+            ]
+        );
+        let lines: Vec<&str> = book.text.lines().collect();
+        assert_eq!(lines[book.toc[1].line], "Chapter 1 Beginnings");
+        assert!(lines.contains(&"A verse."));
+    }
+
+    #[test]
+    fn reads_the_declared_encoding() {
+        let source = NESTED
+            .replace("UTF-8", "windows-1251")
+            .replace("Part One", "Часть первая");
+        let bytes = WINDOWS_1251.encode(&source).0;
+        assert_eq!(declared_encoding(&bytes), WINDOWS_1251);
+        let book = read_fb2(&bytes).unwrap();
+        assert_eq!(book.encoding, WINDOWS_1251);
+        assert_eq!(book.toc[0].title, "Часть первая");
+        assert_eq!(declared_encoding(b"<FictionBook/>"), UTF_8);
+    }
+
+    #[test]
+    fn reads_zipped_books() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("cover.jpg", Default::default()).unwrap();
+        zip.write_all(b"\xFF\xD8").unwrap();
+        zip.start_file("book.fb2", Default::default()).unwrap();
+        zip.write_all(NESTED.as_bytes()).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let book = decode_book(&bytes, Path::new("book.fb2.zip"), None, None).unwrap();
+        assert_eq!(book.toc[0].title, "Part One");
+        assert!(book.text.contains("Two."));
+    }
+
+    #[test]
+    fn reads_back_the_titles_it_writes() {
+        let metadata = Metadata {
+            title: "The Book".to_owned(),
+            author: "Jane Q Doe".to_owned(),
+            language: String::new(),
+        };
+        let part = Division {
+            level: 0,
+            number: 1,
+            title: "Part One",
+        };
+        let mut builder = Fb2Builder::new(&metadata);
+        builder.add_chapter(&chapter(1, &["Part One", "Foreword."]));
+        builder.add_chapter(&Chapter {
+            level: 1,
+            parents: vec![part],
+            ..chapter(1, &["Chapter 1 <of 2>", "One & only."])
+        });
+        builder.add_chapter(&chapter(2, &["Part Two", "Two."]));
+        let document = builder.finish();
+        assert!(document.contains("<middle-name>Q</middle-name>"));
+
+        let book = read_fb2(document.as_bytes()).unwrap();
+        assert_eq!(
+            toc(&book),
+            [("Part One", 0), ("Chapter 1 <of 2>", 1), ("Part Two", 0)]
+        );
+        assert!(book.text.contains("One & only."));
+    }
+}
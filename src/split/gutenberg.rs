@@ -0,0 +1,219 @@
+//! Recognizing the Project Gutenberg header and license around the text of a book.
+//!
+//! Current releases put the text between `*** START OF THE PROJECT GUTENBERG EBOOK ... ***`
+//! and `*** END OF THE PROJECT GUTENBERG EBOOK ... ***` lines. Older ones say `THIS` instead
+//! of `THE`, `ETEXT` instead of `EBOOK`, end the header with the `*END*THE SMALL PRINT!` line
+//! or end the text with `End of Project Gutenberg's ...`.
+
+use super::Metadata;
+use regex::Regex;
+
+/// Header lines are looked for among this many lines from the start of the book.
+const HEADER_LINES: usize = 1000;
+
+/// Languages Project Gutenberg names in full in the `Language:` line, with their BCP 47 tags.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("french", "fr"),
+    ("german", "de"),
+    ("spanish", "es"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("dutch", "nl"),
+    ("finnish", "fi"),
+    ("swedish", "sv"),
+    ("danish", "da"),
+    ("norwegian", "no"),
+    ("polish", "pl"),
+    ("russian", "ru"),
+    ("ukrainian", "uk"),
+    ("czech", "cs"),
+    ("hungarian", "hu"),
+    ("greek", "el"),
+    ("latin", "la"),
+    ("esperanto", "eo"),
+    ("chinese", "zh"),
+    ("japanese", "ja"),
+    ("tagalog", "tl"),
+];
+
+const START_MARKER: &str = r"(?i)^\s*(?:\*+\s*START OF (?:THE|THIS) PROJECT GUTENBERG E-?(?:BOOK|TEXT)|\*END\*\s*THE SMALL PRINT)";
+const END_MARKER: &str = r"(?i)^\s*(?:\*+\s*END OF (?:THE|THIS) PROJECT GUTENBERG E-?(?:BOOK|TEXT)|End of (?:the )?Project Gutenberg)";
+
+/// Index of the start marker line among the first lines of `lines`.
+fn find_start(lines: &[&str]) -> Option<usize> {
+    let marker = Regex::new(START_MARKER).unwrap();
+    lines
+        .iter()
+        .take(HEADER_LINES)
+        .position(|line| marker.is_match(line))
+}
+
+/// Where the text of a Project Gutenberg book lies between the header and the license.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Boundaries {
+    /// Zero-based index of the first line after the start marker.
+    pub start: usize,
+    /// Zero-based index of the end marker line, or the number of lines if there is none.
+    pub end: usize,
+}
+
+/// The start and end of the text in `text`, or `None` if it has no start marker.
+pub fn boundaries(text: &str) -> Option<Boundaries> {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = find_start(&lines)? + 1;
+    let marker = Regex::new(END_MARKER).unwrap();
+    let end = lines[start..]
+        .iter()
+        .position(|line| marker.is_match(line))
+        .map_or(lines.len(), |i| start + i);
+    Some(Boundaries { start, end })
+}
+
+/// Title, author and language from the header of a Project Gutenberg book, empty where the
+/// header does not have them.
+pub fn metadata(text: &str) -> Metadata {
+    let mut metadata = Metadata::default();
+    let lines: Vec<&str> = text.lines().take(HEADER_LINES).collect();
+    let Some(start) = find_start(&lines) else {
+        return metadata;
+    };
+    let header = &lines[..start];
+
+    for (i, line) in header.iter().enumerate() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Long values go on over indented lines.
+        let mut value = value.trim().to_owned();
+        for more in header[i + 1..]
+            .iter()
+            .take_while(|l| l.starts_with(char::is_whitespace) && !l.trim().is_empty())
+        {
+            value.push(' ');
+            value.push_str(more.trim());
+        }
+        match key.trim() {
+            "Title" => metadata.title = value,
+            "Author" => metadata.author = value,
+            "Language" => metadata.language = language_tag(&value),
+            _ => {}
+        }
+    }
+
+    // Older releases only name the book in the first line.
+    if metadata.title.is_empty() {
+        let first_line = Regex::new(
+            r"(?i)Project Gutenberg'?s? E-?(?:BOOK|TEXT) of (?P<title>.+?)(?:, by (?P<author>.+?))?\s*$",
+        )
+        .unwrap();
+        if let Some(captures) = header.iter().find_map(|line| first_line.captures(line)) {
+            metadata.title = captures["title"].trim().to_owned();
+            if metadata.author.is_empty() {
+                if let Some(author) = captures.name("author") {
+                    metadata.author = author.as_str().trim().to_owned();
+                }
+            }
+        }
+    }
+    metadata
+}
+
+/// BCP 47 tag of a language named in English, or the name as it is if it is not known.
+fn language_tag(name: &str) -> String {
+    LANGUAGES
+        .iter()
+        .find(|(language, _)| language.eq_ignore_ascii_case(name))
+        .map_or_else(|| name.to_owned(), |(_, tag)| (*tag).to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::{split_book, Book, SplitConfig};
+
+    const BOOK: &str = "\
+The Project Gutenberg eBook of Moby-Dick; or The Whale
+
+Title: Moby-Dick;
+    or, The Whale
+Author: Herman Melville
+Language: English
+
+*** START OF THE PROJECT GUTENBERG EBOOK MOBY-DICK ***
+Chapter 1
+Call me Ishmael.
+Chapter 2
+The Carpet-Bag.
+*** END OF THE PROJECT GUTENBERG EBOOK MOBY-DICK ***
+Section 1. General Terms of Use.
+";
+
+    #[test]
+    fn finds_the_text_between_the_markers() {
+        assert_eq!(boundaries(BOOK), Some(Boundaries { start: 8, end: 12 }));
+
+        let old = "Header\n\
+            ***START OF THIS PROJECT GUTENBERG ETEXT ALICE***\n\
+            Text\n\
+            End of Project Gutenberg's Alice\n";
+        assert_eq!(boundaries(old), Some(Boundaries { start: 2, end: 3 }));
+        let small_print = "Header\n*END*THE SMALL PRINT! FOR PUBLIC DOMAIN ETEXTS*\nText\n";
+        assert_eq!(
+            boundaries(small_print),
+            Some(Boundaries { start: 2, end: 3 })
+        );
+
+        assert_eq!(boundaries("Chapter 1\nText\n"), None);
+    }
+
+    #[test]
+    fn runs_to_the_end_without_an_end_marker() {
+        let text = "Header\n*** START OF THE PROJECT GUTENBERG EBOOK X ***\nOne\nTwo\n";
+        assert_eq!(boundaries(text), Some(Boundaries { start: 2, end: 4 }));
+    }
+
+    #[test]
+    fn reads_the_metadata_of_the_header() {
+        assert_eq!(
+            metadata(BOOK),
+            Metadata {
+                title: "Moby-Dick; or, The Whale".to_owned(),
+                author: "Herman Melville".to_owned(),
+                language: "en".to_owned(),
+            }
+        );
+        assert_eq!(language_tag("Klingon"), "Klingon");
+        assert_eq!(
+            metadata("Title: Not a Gutenberg book\n"),
+            Metadata::default()
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_first_line() {
+        let old = "The Project Gutenberg EBook of Alice in Wonderland, by Lewis Carroll\n\n\
+            *** START OF THIS PROJECT GUTENBERG EBOOK ALICE ***\n";
+        let metadata = metadata(old);
+        assert_eq!(metadata.title, "Alice in Wonderland");
+        assert_eq!(metadata.author, "Lewis Carroll");
+    }
+
+    #[test]
+    fn writes_the_header_and_license_apart() {
+        let config = SplitConfig::new(r"^Chapter (?P<number>\d+)$");
+        let mut files = Vec::new();
+        split_book(&config, &Book::from_text(BOOK), &mut files, &mut |_| {}).unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "0000_gutenberg_header.txt",
+                "0001.txt",
+                "0002.txt",
+                "9999_gutenberg_license.txt",
+            ]
+        );
+        assert!(String::from_utf8_lossy(&files[3].1).starts_with("*** END OF THE PROJECT"));
+    }
+}
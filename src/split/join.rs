@@ -0,0 +1,426 @@
+//! Joining the chapter files of a split back into one book.
+//!
+//! Files are taken in the order of the manifest of the split if the folder has one, otherwise by
+//! name with numbers compared by value, the Project Gutenberg header and front matter first and
+//! back matter and the license last. `manifest.json` also records what chapter files change about
+//! the book: its encoding, byte order mark and line breaks. Joining the files of an unchanged
+//! split then gives back the book byte for byte, which is checked against its hash.
+
+use super::{encoding, manifest, naming, Boilerplate, Format, MatterOutput, SplitError};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Settings of a join.
+#[derive(Clone, Debug)]
+pub struct JoinConfig {
+    /// Folder a split wrote text chapters to.
+    pub folder: PathBuf,
+    /// File the book is written to.
+    pub output: PathBuf,
+    /// Text put between chapters, after the line break that ends each of them.
+    pub separator: String,
+    /// Encoding of the book, by default that of the split book as recorded in the manifest, or
+    /// else UTF-8.
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// Outcome of a join.
+#[derive(Clone, Debug)]
+pub struct JoinSummary {
+    /// Chapter files joined, relative to the folder, in order.
+    pub files: Vec<String>,
+    /// Size of the book written, in bytes.
+    pub bytes: usize,
+    /// The book is the one split byte for byte. `None` without a `manifest.json` to check it
+    /// against.
+    pub matches_source: Option<bool>,
+    /// Reasons the book cannot match the one split, such as settings that left text out.
+    pub warnings: Vec<String>,
+}
+
+/// The parts of a `manifest.json` a join needs.
+#[derive(serde::Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    source: Source,
+    #[serde(default)]
+    settings: Settings,
+    chapters: Vec<Entry>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct Source {
+    sha256: String,
+    encoding: String,
+    bom: bool,
+    line_ending: String,
+    final_line_break: bool,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self {
+            sha256: String::new(),
+            encoding: String::new(),
+            bom: false,
+            line_ending: "\n".to_owned(),
+            final_line_break: true,
+        }
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct Settings {
+    gutenberg: Boilerplate,
+    remove_page_lines: bool,
+    front_matter: MatterOutput,
+    end_marker: String,
+    back_matter: MatterOutput,
+    reflow: bool,
+    keep_encoding: bool,
+    format: Format,
+}
+
+#[derive(serde::Deserialize)]
+struct Entry {
+    file: String,
+}
+
+/// Join the chapter files in `config.folder` into `config.output`.
+pub fn join_folder(config: &JoinConfig) -> Result<JoinSummary, SplitError> {
+    let json = config
+        .folder
+        .join(manifest::ManifestFormat::Json.file_name());
+    let csv = config
+        .folder
+        .join(manifest::ManifestFormat::Csv.file_name());
+    let manifest = if json.is_file() {
+        let manifest: Manifest = serde_json::from_slice(&std::fs::read(&json)?)
+            .map_err(|e| SplitError::Join(format!("cannot read {}: {e}", json.display())))?;
+        Some(manifest)
+    } else {
+        None
+    };
+    let files = match &manifest {
+        Some(manifest) => {
+            if manifest.settings.format != Format::Text {
+                return Err(SplitError::Join(
+                    "only chapters written as text files can be joined".to_owned(),
+                ));
+            }
+            unique(manifest.chapters.iter().map(|entry| entry.file.clone()))
+        }
+        None if csv.is_file() => csv_files(&String::from_utf8_lossy(&std::fs::read(&csv)?))?,
+        None => {
+            let mut files = Vec::new();
+            list_chapters(&config.folder, "", &config.output, &mut files)?;
+            files
+        }
+    };
+    if files.is_empty() {
+        return Err(SplitError::Join(format!(
+            "no chapter files in {}",
+            config.folder.display()
+        )));
+    }
+
+    let source = manifest.as_ref().map(|manifest| &manifest.source);
+    let source_encoding = source.and_then(|source| Encoding::for_label(source.encoding.as_bytes()));
+    // Chapters are UTF-8 unless the split kept the encoding of the book.
+    let chapter_encoding = manifest.as_ref().map(|manifest| {
+        Some(source_encoding)
+            .filter(|_| manifest.settings.keep_encoding)
+            .flatten()
+            .unwrap_or(UTF_8)
+    });
+    let mut text = String::new();
+    for (i, file) in files.iter().enumerate() {
+        if i > 0 {
+            text.push_str(&config.separator);
+        }
+        let bytes = std::fs::read(config.folder.join(file))?;
+        let encoding = chapter_encoding.unwrap_or_else(|| encoding::detect(&bytes));
+        text.push_str(&encoding::decode(&bytes, encoding));
+    }
+
+    if let Some(source) = source {
+        if source.line_ending == "\r\n" {
+            text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+        }
+        if !source.final_line_break {
+            if let Some(end) = text.strip_suffix(source.line_ending.as_str()) {
+                text.truncate(end.len());
+            }
+        }
+    }
+    let output_encoding = config.encoding.or(source_encoding).unwrap_or(UTF_8);
+    let bom = source.map_or(
+        output_encoding == UTF_16LE || output_encoding == UTF_16BE,
+        |source| source.bom,
+    );
+    let bytes = encoding::encode_with_bom(&text, output_encoding, bom);
+    std::fs::write(&config.output, &bytes)?;
+
+    let mut warnings = Vec::new();
+    if let Some(settings) = manifest.as_ref().map(|manifest| &manifest.settings) {
+        let dropped = [
+            (
+                settings.gutenberg == Boilerplate::Strip,
+                "left out the Project Gutenberg header and license",
+            ),
+            (
+                settings.front_matter == MatterOutput::Discard,
+                "left out the text before the first header",
+            ),
+            (
+                settings.back_matter == MatterOutput::Discard && !settings.end_marker.is_empty(),
+                "left out the back matter",
+            ),
+            (
+                settings.remove_page_lines,
+                "left out page numbers and running heads",
+            ),
+            (settings.reflow, "joined wrapped lines into paragraphs"),
+        ];
+        warnings.extend(
+            dropped.iter().filter(|(set, _)| *set).map(|(_, what)| {
+                format!("The split {what}, so the book cannot match the original")
+            }),
+        );
+    }
+    let matches_source = source
+        .filter(|source| !source.sha256.is_empty())
+        .map(|source| manifest::sha256(&bytes) == source.sha256);
+
+    Ok(JoinSummary {
+        files,
+        bytes: bytes.len(),
+        matches_source,
+        warnings,
+    })
+}
+
+/// `files` without repeats, in order of first appearance.
+fn unique(files: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    files.filter(|file| seen.insert(file.clone())).collect()
+}
+
+/// The files listed in the `file` column of `manifest.csv`.
+fn csv_files(csv: &str) -> Result<Vec<String>, SplitError> {
+    let mut rows = csv.lines().map(csv_row);
+    let column = rows
+        .next()
+        .and_then(|header| header.iter().position(|name| name == "file"))
+        .ok_or_else(|| SplitError::Join("manifest.csv has no file column".to_owned()))?;
+    Ok(unique(rows.filter_map(|mut row| {
+        (column < row.len()).then(|| row.swap_remove(column))
+    })))
+}
+
+/// Fields of a CSV row, quoted ones unquoted.
+fn csv_row(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Add the text files under `folder`/`prefix` to `files` in book order, leaving out `output`.
+fn list_chapters(
+    folder: &Path,
+    prefix: &str,
+    output: &Path,
+    files: &mut Vec<String>,
+) -> Result<(), SplitError> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(folder.join(prefix))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}{name}");
+        let is_dir = entry.file_type()?.is_dir();
+        let is_text = Path::new(&name)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"));
+        if name.starts_with('.') || !is_dir && (!is_text || entry.path() == output) {
+            continue;
+        }
+        let stem = match name.rsplit_once('.') {
+            Some((stem, _)) if !is_dir => stem.to_owned(),
+            _ => name,
+        };
+        entries.push((rank(&stem, is_dir), stem, path, is_dir));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| natural_cmp(&a.1, &b.1)));
+    for (_, _, path, is_dir) in entries {
+        if is_dir {
+            list_chapters(folder, &format!("{path}/"), output, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Where a file or folder goes among its siblings: the Project Gutenberg header, front matter,
+/// chapters, folders of chapters, back matter and the license.
+fn rank(stem: &str, is_dir: bool) -> u8 {
+    match stem {
+        _ if is_dir => 3,
+        naming::GUTENBERG_HEADER_STEM => 0,
+        naming::FRONT_STEM => 1,
+        naming::BACK_STEM => 4,
+        naming::GUTENBERG_LICENSE_STEM => 5,
+        _ => 2,
+    }
+}
+
+/// Compare file stems by their runs of digits as numbers and the rest ignoring case, so that
+/// `9` comes before `10`. Letters right after a number are part letters, so `7z` comes before
+/// `7aa`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn key(stem: &str) -> Vec<(bool, usize, String)> {
+        let mut runs: Vec<(bool, String)> = Vec::new();
+        for c in stem.chars() {
+            let digit = c.is_ascii_digit();
+            match runs.last_mut() {
+                Some((run_digit, run)) if *run_digit == digit => run.push(c),
+                _ => runs.push((digit, c.to_string())),
+            }
+        }
+        let mut key = Vec::new();
+        let mut after_number = false;
+        for (digit, run) in runs {
+            if digit {
+                let number = run.trim_start_matches('0').to_owned();
+                key.push((false, number.len(), number));
+            } else if after_number && run.chars().all(|c| c.is_ascii_lowercase()) {
+                key.push((true, run.len(), run));
+            } else {
+                key.push((true, 0, run.to_lowercase()));
+            }
+            after_number = digit;
+        }
+        key
+    }
+    key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::{split_file, Output, SplitConfig};
+    use encoding_rs::WINDOWS_1251;
+
+    /// Split `book` as `config` says with a JSON manifest, join the chapters back and return
+    /// the joined book along with the summary of the join.
+    fn round_trip(name: &str, book: &[u8], config: SplitConfig) -> (Vec<u8>, JoinSummary) {
+        let folder =
+            std::env::temp_dir().join(format!("book_splitter_join_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("book.txt");
+        std::fs::write(&file, book).unwrap();
+        let chapters = folder.join("chapters");
+        let config = SplitConfig {
+            manifest: Some(manifest::ManifestFormat::Json),
+            output: Output::Folder(chapters.clone()),
+            ..config
+        };
+        let split = split_file(&config, &file, &mut |_| {}).unwrap();
+        assert!(split.files.len() > 2);
+        let output = folder.join("joined.txt");
+        let summary = join_folder(&JoinConfig {
+            folder: chapters,
+            output: output.clone(),
+            separator: String::new(),
+            encoding: None,
+        })
+        .unwrap();
+        let joined = std::fs::read(&output).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        (joined, summary)
+    }
+
+    #[test]
+    fn joins_utf8_back_byte_for_byte() {
+        let book = "Preface.\nChapter 1\nOne — first.\n\nChapter 2\nTwo.\n";
+        let (joined, summary) =
+            round_trip("utf8", book.as_bytes(), SplitConfig::new(r"^Chapter \d+$"));
+        assert_eq!(joined, book.as_bytes());
+        assert_eq!(summary.matches_source, Some(true));
+        assert!(summary.warnings.is_empty());
+    }
+
+    #[test]
+    fn joins_crlf_with_bom_back_byte_for_byte() {
+        let book = b"\xEF\xBB\xBFPreface.\r\nChapter 1\r\nOne.\r\nChapter 2\r\nTwo.";
+        let (joined, summary) = round_trip("crlf", book, SplitConfig::new(r"^Chapter \d+$"));
+        assert_eq!(joined, book);
+        assert_eq!(summary.matches_source, Some(true));
+    }
+
+    #[test]
+    fn joins_cp1251_kept_encoding_back_byte_for_byte() {
+        let text = "Предисловие.\nГлава 1\nПервая глава.\nГлава 2\nВторая глава.\n";
+        let book = WINDOWS_1251.encode(text).0.into_owned();
+        let config = SplitConfig {
+            encoding: Some(WINDOWS_1251),
+            keep_encoding: true,
+            ..SplitConfig::new(r"^Глава \d+$")
+        };
+        let (joined, summary) = round_trip("cp1251", &book, config);
+        assert_eq!(joined, book);
+        assert_eq!(summary.matches_source, Some(true));
+    }
+
+    #[test]
+    fn compares_numbers_by_value() {
+        assert_eq!(natural_cmp("9", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("0009", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("chapter 2", "Chapter 10"), Ordering::Less);
+        assert_eq!(natural_cmp("0007z", "0007aa"), Ordering::Less);
+        assert_eq!(natural_cmp("0007a", "0007b"), Ordering::Less);
+        assert_eq!(natural_cmp("0007", "0007a"), Ordering::Less);
+    }
+
+    #[test]
+    fn puts_matter_around_chapters() {
+        assert!(rank(naming::GUTENBERG_HEADER_STEM, false) < rank(naming::FRONT_STEM, false));
+        assert!(rank(naming::FRONT_STEM, false) < rank("0001", false));
+        assert!(rank("0001", false) < rank("01 Part One", true));
+        assert!(rank("01 Part One", true) < rank(naming::BACK_STEM, false));
+        assert!(rank(naming::BACK_STEM, false) < rank(naming::GUTENBERG_LICENSE_STEM, false));
+    }
+
+    #[test]
+    fn reads_quoted_csv_fields() {
+        assert_eq!(csv_row("1,a,b"), ["1", "a", "b"]);
+        assert_eq!(
+            csv_row(r#"2,"Part One, ""The Start""",0002.txt"#),
+            ["2", r#"Part One, "The Start""#, "0002.txt"]
+        );
+        assert_eq!(csv_row("3,,"), ["3", "", ""]);
+        assert_eq!(
+            csv_files("number,file\r\n1,0001.txt\r\n1a,0001.txt\r\n2,\"b,c.txt\"\r\n").unwrap(),
+            ["0001.txt", "b,c.txt"]
+        );
+        assert!(csv_files("number,title\n1,x\n").is_err());
+    }
+}
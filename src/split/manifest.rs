@@ -0,0 +1,326 @@
+//! A record of a split for tools working with its output: where every chapter came from in
+//! the book, which file it went to, and the settings that cut it.
+
+use super::{
+    encoding, Boilerplate, Book, Chapter, Format, Level, MatterOutput, Numbering, SizeLimit,
+    SplitBy, SplitConfig,
+};
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+
+/// What the manifest is written as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ManifestFormat {
+    /// `manifest.json` with the source, the settings and the chapters.
+    Json,
+    /// `manifest.csv`, a table of the chapters alone.
+    Csv,
+}
+
+impl ManifestFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "manifest.json",
+            ManifestFormat::Csv => "manifest.csv",
+        }
+    }
+}
+
+/// The book a split was made from.
+#[derive(serde::Serialize)]
+struct Source<'a> {
+    /// Path of the book as given, if it was read from a file.
+    file: Option<String>,
+    /// [`Book::sha256`]: SHA-256 of the file as unpacked, hex encoded.
+    sha256: &'a str,
+    encoding: &'static str,
+    /// The file starts with a byte order mark.
+    bom: bool,
+    /// Line break used by most lines, which chapter files replace with `\n`.
+    line_ending: &'static str,
+    /// The last line of the file ends with a line break, which chapter files always do.
+    final_line_break: bool,
+}
+
+/// The parts of [`SplitConfig`] that decide what the chapters are.
+#[derive(serde::Serialize)]
+struct Settings<'a> {
+    split_by: SplitBy,
+    pattern: &'a str,
+    levels: &'a [Level],
+    numbering: Numbering,
+    toc_depth: usize,
+    start_chapter: usize,
+    size_limit: Option<&'a SizeLimit>,
+    gutenberg: Boilerplate,
+    remove_page_lines: bool,
+    front_matter: MatterOutput,
+    end_marker: &'a str,
+    back_matter: MatterOutput,
+    reflow: bool,
+    name_template: &'a str,
+    nested_folders: bool,
+    keep_encoding: bool,
+    format: Format,
+}
+
+/// A chapter as listed in the manifest.
+#[derive(serde::Serialize)]
+struct Entry {
+    /// The number as in file names, `front` and such for front and back matter.
+    number: String,
+    /// The header line, empty for text without one.
+    header: String,
+    title: String,
+    /// File the chapter was written to, or the book holding it for single-file formats.
+    file: String,
+    /// One-based numbers of the first and last line of the chapter in the book.
+    first_line: usize,
+    last_line: usize,
+    /// Byte range of the chapter in the book file once unpacked from gzip, bzip2 or ZIP, counting
+    /// a BOM if it has one. For EPUB and FB2 books it is not in the file but in the decoded
+    /// text taken out of their markup, as UTF-8.
+    start_byte: usize,
+    end_byte: usize,
+    words: usize,
+    chars: usize,
+}
+
+#[derive(serde::Serialize)]
+struct Document<'a> {
+    generator: &'static str,
+    source: Source<'a>,
+    settings: Settings<'a>,
+    chapters: &'a [Entry],
+}
+
+/// Collects the chapters of a split and writes them out as a manifest.
+pub struct ManifestBuilder<'a> {
+    pub format: ManifestFormat,
+    book: &'a Book,
+    /// Byte offsets of the lines of the book text, with the end of the text last.
+    line_starts: Vec<usize>,
+    /// Offsets of the same lines in the book file.
+    source_starts: Vec<usize>,
+    entries: Vec<Entry>,
+}
+
+impl<'a> ManifestBuilder<'a> {
+    pub fn new(book: &'a Book, format: ManifestFormat) -> Self {
+        let mut line_starts = vec![0];
+        let mut source_starts = vec![encoding::encode_with_bom("", book.encoding, book.bom).len()];
+        for line in book.text.split_inclusive('\n') {
+            line_starts.push(line_starts[line_starts.len() - 1] + line.len());
+            source_starts.push(
+                source_starts[source_starts.len() - 1] + encoding::encoded_len(line, book.encoding),
+            );
+        }
+        Self {
+            format,
+            book,
+            line_starts,
+            source_starts,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add `chapter`, written to `file`. Call before the chapter is reflowed: its lines must
+    /// still point into the book text.
+    pub fn add_chapter(&mut self, chapter: &Chapter<'_>, file: &str) {
+        let span = text_span(&self.book.text, &chapter.lines).unwrap_or(0..0);
+        let last = span.end.saturating_sub(1).max(span.start);
+        self.entries.push(Entry {
+            number: chapter.number_label(0),
+            header: chapter.header.unwrap_or_default().trim().to_owned(),
+            title: chapter.title.unwrap_or_default().trim().to_owned(),
+            file: file.to_owned(),
+            first_line: self.line_of(span.start) + 1,
+            last_line: self.line_of(last) + 1,
+            start_byte: self.source_offset(span.start),
+            end_byte: self.source_offset(span.end),
+            words: chapter.text().split_whitespace().count(),
+            chars: chapter.char_count(),
+        });
+    }
+
+    /// Name the file of chapters added without one, for formats writing a single book. Does
+    /// nothing for formats writing a file per chapter.
+    pub fn set_book_file(&mut self, file: &str) {
+        for entry in self.entries.iter_mut().filter(|e| e.file.is_empty()) {
+            entry.file = file.to_owned();
+        }
+    }
+
+    /// The manifest of a split with `config`.
+    pub fn finish(&self, config: &SplitConfig) -> Vec<u8> {
+        match self.format {
+            ManifestFormat::Json => {
+                let document = Document {
+                    generator: concat!("Book Splitter ", env!("CARGO_PKG_VERSION")),
+                    source: Source {
+                        file: self
+                            .book
+                            .path
+                            .as_ref()
+                            .map(|path| path.display().to_string()),
+                        sha256: &self.book.sha256,
+                        encoding: self.book.encoding.name(),
+                        bom: self.book.bom,
+                        line_ending: line_ending(&self.book.text),
+                        final_line_break: self.book.text.ends_with('\n'),
+                    },
+                    settings: Settings {
+                        split_by: config.split_by,
+                        pattern: &config.pattern,
+                        levels: &config.levels,
+                        numbering: config.numbering,
+                        toc_depth: config.toc_depth,
+                        start_chapter: config.start_chapter,
+                        size_limit: config.size_limit.as_ref(),
+                        gutenberg: config.gutenberg,
+                        remove_page_lines: config.remove_page_lines,
+                        front_matter: config.front_matter,
+                        end_marker: &config.end_marker,
+                        back_matter: config.back_matter,
+                        reflow: config.reflow,
+                        name_template: &config.naming.template,
+                        nested_folders: config.naming.nested,
+                        keep_encoding: config.keep_encoding,
+                        format: config.format,
+                    },
+                    chapters: &self.entries,
+                };
+                let mut json = serde_json::to_vec_pretty(&document).unwrap();
+                json.push(b'\n');
+                json
+            }
+            ManifestFormat::Csv => {
+                let mut csv = String::from(
+                    "number,header,title,file,first_line,last_line,start_byte,end_byte,words,chars\r\n",
+                );
+                for e in &self.entries {
+                    csv.push_str(&format!(
+                        "{},{},{},{},{},{},{},{},{},{}\r\n",
+                        csv_field(&e.number),
+                        csv_field(&e.header),
+                        csv_field(&e.title),
+                        csv_field(&e.file),
+                        e.first_line,
+                        e.last_line,
+                        e.start_byte,
+                        e.end_byte,
+                        e.words,
+                        e.chars
+                    ));
+                }
+                csv.into_bytes()
+            }
+        }
+    }
+
+    /// Zero-based index of the line holding byte `offset` of the book text.
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Offset in the book file of byte `offset` of the book text.
+    fn source_offset(&self, offset: usize) -> usize {
+        let line = self.line_of(offset);
+        let within = &self.book.text[self.line_starts[line]..offset];
+        self.source_starts[line] + encoding::encoded_len(within, self.book.encoding)
+    }
+}
+
+/// Byte range of `text` that `lines` cover along with the line break after the last one, if
+/// they are slices of it.
+fn text_span(text: &str, lines: &[&str]) -> Option<Range<usize>> {
+    let offset = |line: &str| {
+        let offset = (line.as_ptr() as usize).checked_sub(text.as_ptr() as usize)?;
+        (offset + line.len() <= text.len()).then_some(offset)
+    };
+    let first = lines.first()?;
+    let last = lines.last()?;
+    let end = offset(last)? + last.len();
+    let line_break = ["\r\n", "\n"]
+        .iter()
+        .find(|line_break| text[end..].starts_with(**line_break))
+        .map_or(0, |line_break| line_break.len());
+    Some(offset(first)?..end + line_break)
+}
+
+/// `\r\n` if most line breaks of `text` are, otherwise `\n`.
+fn line_ending(text: &str) -> &'static str {
+    if text.matches("\r\n").count() * 2 > text.matches('\n').count() {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// SHA-256 of `bytes`, hex encoded.
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Quote `value` if it holds a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::{chapters, decode_book};
+    use encoding_rs::{UTF_16LE, UTF_8};
+    use std::path::Path;
+
+    /// The chapters of `bytes` as the manifest locates them in the file, decoded, next to the
+    /// chapters themselves.
+    fn located(bytes: &[u8]) -> Vec<(String, String)> {
+        let book = decode_book(bytes, Path::new("book.txt"), None, None).unwrap();
+        let chapters = chapters(&SplitConfig::new(r"^Chapter \d+$"), &book).unwrap();
+        let mut builder = ManifestBuilder::new(&book, ManifestFormat::Json);
+        for chapter in &chapters {
+            builder.add_chapter(chapter, "");
+        }
+        builder
+            .entries
+            .iter()
+            .zip(&chapters)
+            .map(|(entry, chapter)| {
+                let slice = &bytes[entry.start_byte..entry.end_byte];
+                let text = book.encoding.decode_without_bom_handling(slice).0;
+                (text.replace("\r\n", "\n"), chapter.text())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn locates_chapters_in_the_book_file() {
+        let text = "Chapter 1\r\nÉté.\r\nChapter 2\r\nDeux.\r\n";
+        for (encoding, bom) in [(UTF_8, false), (UTF_8, true), (UTF_16LE, true)] {
+            let bytes = encoding::encode_with_bom(text, encoding, bom);
+            let located = located(&bytes);
+            assert_eq!(located.len(), 2);
+            for (slice, chapter) in located {
+                assert_eq!(slice, chapter, "{} with BOM {bom}", encoding.name());
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("Chapter 1"), "Chapter 1");
+        assert_eq!(csv_field("One, Two"), "\"One, Two\"");
+        assert_eq!(csv_field("The \"End\""), "\"The \"\"End\"\"\"");
+        assert_eq!(csv_field("Two\nlines"), "\"Two\nlines\"");
+        assert_eq!(csv_field("Two\r\nlines"), "\"Two\r\nlines\"");
+    }
+}
@@ -0,0 +1,234 @@
+//! Chapters as Markdown files, for static site generators and note-taking apps.
+//!
+//! Like EPUB chapters, the header becomes a heading one level deeper for every part it lies
+//! under and every other line a paragraph of its own.
+
+use super::Chapter;
+
+/// How chapters are written as Markdown.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Markdown {
+    /// Put a backslash before characters Markdown would read as formatting, such as `*` and
+    /// `_`, so that the text shows as it is in the book.
+    pub escape: bool,
+    /// Start every file with YAML front matter: the chapter number and title, the book, the
+    /// word count and the files before and after it.
+    pub front_matter: bool,
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Self {
+            escape: true,
+            front_matter: true,
+        }
+    }
+}
+
+/// The Markdown file `file` holding `chapter` of `book`, between the files `prev` and `next`.
+pub fn chapter_document(
+    options: &Markdown,
+    chapter: &Chapter<'_>,
+    book: Option<&str>,
+    file: &str,
+    prev: Option<&str>,
+    next: Option<&str>,
+) -> String {
+    let mut document = String::new();
+    if options.front_matter {
+        let number = chapter.number_label(0);
+        let number = if number.bytes().all(|b| b.is_ascii_digit()) {
+            number
+        } else {
+            yaml_string(&number)
+        };
+        let title = chapter.title.map(str::trim);
+        let link = |other: Option<&str>| other.map(|other| relative(file, other));
+        document.push_str("---\n");
+        document.push_str(&format!("number: {number}\n"));
+        document.push_str(&format!("title: {}\n", yaml_value(title)));
+        document.push_str(&format!("book: {}\n", yaml_value(book)));
+        let words = chapter.text().split_whitespace().count();
+        document.push_str(&format!("words: {words}\n"));
+        document.push_str(&format!("prev: {}\n", yaml_value(link(prev).as_deref())));
+        document.push_str(&format!("next: {}\n", yaml_value(link(next).as_deref())));
+        document.push_str("---\n\n");
+    }
+
+    let text = |line: &str| {
+        if options.escape {
+            escape(line)
+        } else {
+            line.to_owned()
+        }
+    };
+    let mut blocks = Vec::new();
+    let mut lines = chapter.lines.iter();
+    if let Some(header) = chapter.header {
+        lines.next();
+        let mut heading = text(header.trim());
+        // A `#` ending a heading would be taken for a closing sequence.
+        if options.escape && heading.ends_with('#') {
+            heading.insert(heading.len() - 1, '\\');
+        }
+        let level = (chapter.parents.len() + 1).min(6);
+        blocks.push(format!("{} {heading}", "#".repeat(level)));
+    }
+    blocks.extend(
+        lines
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(text),
+    );
+    document.push_str(&blocks.join("\n\n"));
+    document.push('\n');
+    document
+}
+
+/// `line` with a backslash before characters that Markdown reads as formatting anywhere, and
+/// before the ones that start a heading, list or rule at the start of a line.
+fn escape(line: &str) -> String {
+    let mut escaped = String::with_capacity(line.len() + 8);
+    if line.starts_with(['#', '+', '-', '=']) {
+        escaped.push('\\');
+    }
+    // `1.` or `1)` would start a numbered list.
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    for (i, c) in line.char_indices() {
+        let list_number = digits > 0 && i == digits && matches!(c, '.' | ')');
+        if list_number
+            || matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~'
+            )
+        {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `value` as a double-quoted YAML string, or `null`.
+fn yaml_value(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_owned(), yaml_string)
+}
+
+/// JSON strings are YAML double-quoted strings too.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// Path of the file `to` relative to the folder of the file `from`, both relative to the same
+/// folder with `/` separators.
+fn relative(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').collect();
+    let to: Vec<&str> = to.split('/').collect();
+    let from_dirs = &from[..from.len() - 1];
+    let to_dirs = &to[..to.len() - 1];
+    let common = from_dirs
+        .iter()
+        .zip(to_dirs)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = "../".repeat(from_dirs.len() - common);
+    path.push_str(&to[common..].join("/"));
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::tests::chapter;
+
+    #[test]
+    fn quotes_the_front_matter() {
+        let title = r#"- Part: "One" #1"#;
+        let chapter = Chapter {
+            title: Some(title),
+            ..chapter(1, &[title, "Text."])
+        };
+        let document = chapter_document(
+            &Markdown::default(),
+            &chapter,
+            Some("Book: A #Tale"),
+            "0001.md",
+            None,
+            Some("0002.md"),
+        );
+        let front_matter: Vec<&str> = document.lines().take(8).collect();
+        assert_eq!(
+            front_matter,
+            [
+                "---",
+                "number: 1",
+                r#"title: "- Part: \"One\" #1""#,
+                r#"book: "Book: A #Tale""#,
+                "words: 5",
+                "prev: null",
+                r#"next: "0002.md""#,
+                "---",
+            ]
+        );
+        assert!(document.contains("\n# \\- Part: \"One\" #1\n"));
+    }
+
+    #[test]
+    fn escapes_formatting_at_the_start_of_lines() {
+        let lines = [
+            "# not a heading",
+            "- not a list",
+            "+ nor this",
+            "= nor a rule",
+            "1. not numbered",
+            "12) nor this",
+            "> not a quote",
+            "*stars* and _underscores_",
+            "1999 was a year.",
+        ];
+        let escaped: Vec<String> = lines.iter().map(|line| escape(line)).collect();
+        assert_eq!(
+            escaped,
+            [
+                r"\# not a heading",
+                r"\- not a list",
+                r"\+ nor this",
+                r"\= nor a rule",
+                r"1\. not numbered",
+                r"12\) nor this",
+                r"\> not a quote",
+                r"\*stars\* and \_underscores\_",
+                "1999 was a year.",
+            ]
+        );
+        let options = Markdown {
+            escape: false,
+            front_matter: false,
+        };
+        let document = chapter_document(
+            &options,
+            &chapter(1, &["Chapter 1", "# kept"]),
+            None,
+            "a.md",
+            None,
+            None,
+        );
+        assert_eq!(document, "# Chapter 1\n\n# kept\n");
+    }
+
+    #[test]
+    fn links_across_folders() {
+        assert_eq!(relative("0001.md", "0002.md"), "0002.md");
+        assert_eq!(
+            relative("part_01/0009.md", "part_02/0001.md"),
+            "../part_02/0001.md"
+        );
+        assert_eq!(
+            relative("book/part_01/0001.md", "book/part_01/0002.md"),
+            "0002.md"
+        );
+        assert_eq!(relative("part_01/a/0001.md", "0000.md"), "../../0000.md");
+        assert_eq!(relative("0000.md", "part_01/0001.md"), "part_01/0001.md");
+    }
+}
@@ -0,0 +1,430 @@
+//! Names of the chapter files.
+//!
+//! A [`Naming`] template is filled in with the number, the title and the groups of the header
+//! regex of every chapter. The names are made safe on every common file system and kept unique
+//! within a split, telling apart names that differ only in case.
+
+use super::{Chapter, Matter, SplitError};
+use regex::{Captures, Regex};
+use std::collections::HashSet;
+
+/// Longest file name stem we produce, in characters, leaving room for collision suffixes and
+/// extensions within the usual 255 byte limit.
+const MAX_STEM: usize = 100;
+
+/// Names of front and back matter written to files of their own, sorting before and after the
+/// chapters.
+pub const FRONT_STEM: &str = "0000_front";
+pub const BACK_STEM: &str = "9999_back";
+pub const GUTENBERG_HEADER_STEM: &str = "0000_gutenberg_header";
+pub const GUTENBERG_LICENSE_STEM: &str = "9999_gutenberg_license";
+
+/// Names Windows reserves for devices, whatever the extension.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// How chapter files are named, as a template with `{placeholder}`s:
+///
+/// - `{n}` the chapter number, `{n:4}` padded with zeros to 4 digits, with a letter for parts
+///   of a chapter cut by size as in `0007b`
+/// - `{title}` the chapter title (see [`Chapter::title`](super::Chapter::title)) and `{slug}` a
+///   lowercase, dash-separated form of it
+/// - `{1}`, `{2}`, ... and `{name}` groups captured by the header regex
+///
+/// A width after a text placeholder, as in `{title:30}`, caps its length. `{{` and `}}` are
+/// literal braces and `/` separates folders. The extension is added by the output format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Naming {
+    pub template: String,
+    /// Put chapters into a folder per header of an outer level they lie under, such as
+    /// `01 Part One/0003.txt`. The text between such a header and the first chapter under it
+    /// goes into its folder as chapter 0.
+    pub nested: bool,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Self {
+            template: "{n:4}".to_owned(),
+            nested: false,
+        }
+    }
+}
+
+impl Naming {
+    /// Parse the template, ready to name the chapters of one split. Groups must be captured by
+    /// one of the `headers` regexes, of which there are none when splitting by table of contents
+    /// or size.
+    pub fn compile(&self, headers: &[Regex]) -> Result<NameTemplate, SplitError> {
+        let invalid =
+            |reason: &str| SplitError::Template(format!("{reason} in {:?}", self.template));
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = self.template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(invalid("unclosed `{`")),
+                        }
+                    }
+                    let (key, width) = match placeholder.split_once(':') {
+                        Some((key, width)) => match width.parse() {
+                            Ok(width) => (key, Some(width)),
+                            Err(_) => return Err(invalid("bad width")),
+                        },
+                        None => (placeholder.as_str(), None),
+                    };
+                    if key.is_empty() {
+                        return Err(invalid("empty placeholder"));
+                    }
+                    let group = |header: &Regex| match key.parse::<usize>() {
+                        Ok(index) => index < header.captures_len(),
+                        Err(_) => header.capture_names().flatten().any(|name| name == key),
+                    };
+                    if !matches!(key, "n" | "title" | "slug") && !headers.iter().any(group) {
+                        return Err(invalid(&format!(
+                            "`{{{key}}}` is neither a placeholder nor a group of the header regex"
+                        )));
+                    }
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    segments.push(Segment::Placeholder(key.to_owned(), width));
+                }
+                '}' => return Err(invalid("unmatched `}`")),
+                c => literal.push(c),
+            }
+        }
+        segments.push(Segment::Literal(literal));
+
+        Ok(NameTemplate {
+            segments,
+            nested: self.nested,
+            used: HashSet::new(),
+        })
+    }
+}
+
+enum Segment {
+    Literal(String),
+    /// Key and width.
+    Placeholder(String, Option<usize>),
+}
+
+/// A parsed [`Naming`] template. Remembers the names it gave to keep them unique.
+pub struct NameTemplate {
+    segments: Vec<Segment>,
+    nested: bool,
+    /// Lowercased names already given, since Windows and macOS ignore case.
+    used: HashSet<String>,
+}
+
+impl NameTemplate {
+    /// File name of `chapter`, with `captures` of the header regex if it matched one.
+    ///
+    /// Names that are taken get `-2`, `-3` and so on appended to the stem.
+    pub fn file_name(
+        &mut self,
+        chapter: &Chapter<'_>,
+        captures: Option<&Captures<'_>>,
+        extension: &str,
+    ) -> String {
+        match chapter.matter {
+            Matter::Front => return self.unique(FRONT_STEM, extension),
+            Matter::Back => return self.unique(BACK_STEM, extension),
+            Matter::GutenbergHeader => return self.unique(GUTENBERG_HEADER_STEM, extension),
+            Matter::GutenbergLicense => return self.unique(GUTENBERG_LICENSE_STEM, extension),
+            Matter::Body => {}
+        }
+        let number = if self.nested && chapter.division {
+            0
+        } else {
+            chapter.number
+        };
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => name.push_str(text),
+                Segment::Placeholder(key, width) => {
+                    name.push_str(&placeholder(chapter, number, captures, key, *width))
+                }
+            }
+        }
+
+        let components: Vec<String> = name
+            .split('/')
+            .map(sanitize)
+            .filter(|component| !component.is_empty())
+            .collect();
+        let mut stem = if components.is_empty() {
+            number.to_string()
+        } else {
+            components.join("/")
+        };
+        if self.nested {
+            let own = chapter
+                .division
+                .then(|| (chapter.number, chapter.title.unwrap_or_default()));
+            let mut path: Vec<String> = chapter
+                .parents
+                .iter()
+                .map(|parent| (parent.number, parent.title))
+                .chain(own)
+                .map(|(number, title)| sanitize(&format!("{number:02} {}", title.trim())))
+                .collect();
+            path.push(stem);
+            stem = path.join("/");
+        }
+        self.unique(&stem, extension)
+    }
+
+    fn unique(&mut self, stem: &str, extension: &str) -> String {
+        let mut name = format!("{stem}.{extension}");
+        let mut counter = 1;
+        while !self.used.insert(name.to_lowercase()) {
+            counter += 1;
+            name = format!("{stem}-{counter}.{extension}");
+        }
+        name
+    }
+}
+
+fn placeholder(
+    chapter: &Chapter<'_>,
+    number: usize,
+    captures: Option<&Captures<'_>>,
+    key: &str,
+    width: Option<usize>,
+) -> String {
+    if key == "n" {
+        return number_label(number, chapter.part, width.unwrap_or(0));
+    }
+
+    let title = chapter.title.unwrap_or_default().trim();
+    let value = match key {
+        "title" => title.to_owned(),
+        "slug" => slug(title),
+        key => {
+            let group = match key.parse::<usize>() {
+                Ok(index) => captures.and_then(|c| c.get(index)),
+                Err(_) => captures.and_then(|c| c.name(key)),
+            };
+            group.map_or("", |m| m.as_str()).trim().to_owned()
+        }
+    };
+    // Values must not sneak in folders.
+    let value = value.replace(['/', '\\'], "-");
+    match width {
+        Some(width) => {
+            let value: String = value.chars().take(width).collect();
+            value.trim_end_matches(['-', ' ']).to_owned()
+        }
+        None => value,
+    }
+}
+
+/// `number` padded with zeros to `width` digits, followed by a letter for a `part`.
+pub fn number_label(number: usize, part: Option<usize>, width: usize) -> String {
+    let mut label = format!("{number:0width$}");
+    if let Some(part) = part {
+        let mut letters = Vec::new();
+        let mut rest = part + 1;
+        while rest > 0 {
+            rest -= 1;
+            letters.push(char::from(b'a' + (rest % 26) as u8));
+            rest /= 26;
+        }
+        label.extend(letters.iter().rev());
+    }
+    label
+}
+
+/// Lowercase `text` and join its words with dashes.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Make `name` safe as a file name on Windows, macOS and Linux: replace forbidden and control
+/// characters, trim trailing dots and spaces, avoid reserved device names and cap the length.
+pub fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_STEM)
+        .collect();
+    let name = name.trim().trim_end_matches(['.', ' ']);
+    if name == "." || name == ".." {
+        return String::new();
+    }
+
+    let base = name.split('.').next().unwrap_or_default();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+        format!("{base}_{}", &name[base.len()..])
+    } else {
+        name.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::tests::chapter;
+
+    fn naming(template: &str) -> NameTemplate {
+        Naming {
+            template: template.to_owned(),
+            nested: false,
+        }
+        .compile(&[])
+        .unwrap()
+    }
+
+    #[test]
+    fn sanitizes_reserved_names() {
+        assert_eq!(sanitize("CON"), "CON_");
+        assert_eq!(sanitize("nul.txt"), "nul_.txt");
+        assert_eq!(sanitize("Lpt1"), "Lpt1_");
+        assert_eq!(sanitize("CONSOLE"), "CONSOLE");
+    }
+
+    #[test]
+    fn sanitizes_separators_and_forbidden_characters() {
+        assert_eq!(sanitize("a/b\\c"), "a_b_c");
+        assert_eq!(
+            sanitize("What? <Why>: \"Now\" | *"),
+            "What_ _Why__ _Now_ _ _"
+        );
+        assert_eq!(sanitize("tab\there"), "tab_here");
+    }
+
+    #[test]
+    fn sanitizes_trailing_dots_and_spaces() {
+        assert_eq!(sanitize("The End... "), "The End");
+        assert_eq!(sanitize(" spaced "), "spaced");
+        assert_eq!(sanitize(".."), "");
+        assert_eq!(sanitize("."), "");
+        assert_eq!(sanitize(&"x".repeat(300)).len(), MAX_STEM);
+    }
+
+    #[test]
+    fn suffixes_names_taken_ignoring_case() {
+        let mut names = naming("{title}");
+        assert_eq!(
+            names.file_name(&chapter(1, &["Intro"]), None, "txt"),
+            "Intro.txt"
+        );
+        assert_eq!(
+            names.file_name(&chapter(2, &["intro"]), None, "txt"),
+            "intro-2.txt"
+        );
+        assert_eq!(
+            names.file_name(&chapter(3, &["INTRO"]), None, "txt"),
+            "INTRO-3.txt"
+        );
+        assert_eq!(
+            names.file_name(&chapter(4, &["Other"]), None, "txt"),
+            "Other.txt"
+        );
+    }
+
+    #[test]
+    fn keeps_titles_from_adding_folders() {
+        let mut names = naming("{n:2} {title}");
+        assert_eq!(
+            names.file_name(&chapter(3, &["Either/Or"]), None, "txt"),
+            "03 Either-Or.txt"
+        );
+        let mut names = naming("part/{n}");
+        assert_eq!(names.file_name(&chapter(3, &[""]), None, "md"), "part/3.md");
+        let mut names = naming("{title}");
+        assert_eq!(names.file_name(&chapter(5, &["..."]), None, "txt"), "5.txt");
+    }
+
+    #[test]
+    fn names_matter_by_its_stem() {
+        let mut names = naming("{n:4}");
+        let front = Chapter {
+            matter: Matter::Front,
+            ..chapter(1, &[""])
+        };
+        assert_eq!(names.file_name(&front, None, "txt"), "0000_front.txt");
+        assert_eq!(names.file_name(&chapter(0, &[""]), None, "txt"), "0000.txt");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders_and_groups() {
+        let compile = |template: &str, headers: &[Regex]| {
+            Naming {
+                template: template.to_owned(),
+                nested: false,
+            }
+            .compile(headers)
+        };
+        let header = Regex::new(r"^Chapter (?P<number>\d+)(?: (.+))?$").unwrap();
+        for template in ["{n:4} {titel}", "{n} {3}", "{n} {name}"] {
+            assert!(matches!(
+                compile(template, std::slice::from_ref(&header)),
+                Err(SplitError::Template(_))
+            ));
+        }
+        assert!(compile("{n} {2} {number} {0}", std::slice::from_ref(&header)).is_ok());
+        assert!(matches!(
+            compile("{n} {1}", &[]),
+            Err(SplitError::Template(_))
+        ));
+        assert!(compile("{n:4} {title} {slug:20}", &[]).is_ok());
+    }
+
+    #[test]
+    fn fills_in_header_groups() {
+        let header = Regex::new(r"^Chapter (?P<number>\d+): (.+)$").unwrap();
+        let mut names = Naming {
+            template: "{number:3}_{2}".to_owned(),
+            nested: false,
+        }
+        .compile(std::slice::from_ref(&header))
+        .unwrap();
+        let line = "Chapter 12: Nul";
+        let captures = header.captures(line);
+        assert_eq!(
+            names.file_name(&chapter(12, &[line]), captures.as_ref(), "txt"),
+            "12_Nul.txt"
+        );
+        let mut names = naming("{title}");
+        assert_eq!(
+            names.file_name(&chapter(1, &["nul.txt"]), None, "txt"),
+            "nul_.txt.txt"
+        );
+    }
+
+    #[test]
+    fn slugs_titles() {
+        assert_eq!(slug("The Boy Who Lived!"), "the-boy-who-lived");
+        assert_eq!(slug("  Глава — Первая "), "глава-первая");
+    }
+}
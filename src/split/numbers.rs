@@ -0,0 +1,309 @@
+//! Reading chapter numbers written as digits, Roman numerals or words.
+
+/// English number words and their values.
+const ENGLISH: &[(&str, usize)] = &[
+    ("zero", 0),
+    ("one", 1),
+    ("first", 1),
+    ("two", 2),
+    ("second", 2),
+    ("three", 3),
+    ("third", 3),
+    ("four", 4),
+    ("fourth", 4),
+    ("five", 5),
+    ("fifth", 5),
+    ("six", 6),
+    ("sixth", 6),
+    ("seven", 7),
+    ("seventh", 7),
+    ("eight", 8),
+    ("eighth", 8),
+    ("nine", 9),
+    ("ninth", 9),
+    ("ten", 10),
+    ("tenth", 10),
+    ("eleven", 11),
+    ("eleventh", 11),
+    ("twelve", 12),
+    ("twelfth", 12),
+    ("thirteen", 13),
+    ("thirteenth", 13),
+    ("fourteen", 14),
+    ("fourteenth", 14),
+    ("fifteen", 15),
+    ("fifteenth", 15),
+    ("sixteen", 16),
+    ("sixteenth", 16),
+    ("seventeen", 17),
+    ("seventeenth", 17),
+    ("eighteen", 18),
+    ("eighteenth", 18),
+    ("nineteen", 19),
+    ("nineteenth", 19),
+    ("twenty", 20),
+    ("twentieth", 20),
+    ("thirty", 30),
+    ("thirtieth", 30),
+    ("forty", 40),
+    ("fortieth", 40),
+    ("fifty", 50),
+    ("fiftieth", 50),
+    ("sixty", 60),
+    ("sixtieth", 60),
+    ("seventy", 70),
+    ("seventieth", 70),
+    ("eighty", 80),
+    ("eightieth", 80),
+    ("ninety", 90),
+    ("ninetieth", 90),
+];
+
+/// Russian cardinal number words and their values.
+const RUSSIAN: &[(&str, usize)] = &[
+    ("ноль", 0),
+    ("один", 1),
+    ("одна", 1),
+    ("одно", 1),
+    ("два", 2),
+    ("две", 2),
+    ("три", 3),
+    ("четыре", 4),
+    ("пять", 5),
+    ("шесть", 6),
+    ("семь", 7),
+    ("восемь", 8),
+    ("девять", 9),
+    ("десять", 10),
+    ("одиннадцать", 11),
+    ("двенадцать", 12),
+    ("тринадцать", 13),
+    ("четырнадцать", 14),
+    ("пятнадцать", 15),
+    ("шестнадцать", 16),
+    ("семнадцать", 17),
+    ("восемнадцать", 18),
+    ("девятнадцать", 19),
+    ("двадцать", 20),
+    ("тридцать", 30),
+    ("сорок", 40),
+    ("пятьдесят", 50),
+    ("шестьдесят", 60),
+    ("семьдесят", 70),
+    ("восемьдесят", 80),
+    ("девяносто", 90),
+    ("сто", 100),
+    ("двести", 200),
+    ("триста", 300),
+    ("четыреста", 400),
+    ("пятьсот", 500),
+];
+
+/// Stems of Russian ordinal numbers, which take gender and case endings.
+const RUSSIAN_ORDINALS: &[(&str, usize)] = &[
+    ("перв", 1),
+    ("втор", 2),
+    ("трет", 3),
+    ("четверт", 4),
+    ("четвёрт", 4),
+    ("пят", 5),
+    ("шест", 6),
+    ("седьм", 7),
+    ("восьм", 8),
+    ("девят", 9),
+    ("десят", 10),
+    ("одиннадцат", 11),
+    ("двенадцат", 12),
+    ("тринадцат", 13),
+    ("четырнадцат", 14),
+    ("пятнадцат", 15),
+    ("шестнадцат", 16),
+    ("семнадцат", 17),
+    ("восемнадцат", 18),
+    ("девятнадцат", 19),
+    ("двадцат", 20),
+    ("тридцат", 30),
+    ("сороков", 40),
+    ("пятидесят", 50),
+    ("шестидесят", 60),
+    ("семидесят", 70),
+    ("восьмидесят", 80),
+    ("девяност", 90),
+    ("сот", 100),
+];
+
+/// Roman numerals stop short of this.
+const MAX_ROMAN: usize = 400;
+
+const RUSSIAN_ENDINGS: &[&str] = &["ый", "ой", "ий", "ая", "ое", "ья", "ье", "ие", "ые"];
+
+/// Parse a chapter number written as digits (`17`), a Roman numeral (`XVII`) or in English or
+/// Russian words (`seventeen`, `Twenty-First`, `семнадцатая`).
+pub fn parse_number(text: &str) -> Option<usize> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    text.parse()
+        .ok()
+        .or_else(|| parse_roman(text))
+        .or_else(|| parse_words(text))
+}
+
+/// Parse a Roman numeral in canonical form and a single case, like `XIV` or `xiv` but not
+/// `IIII`, `IC` or `Xiv`. Numerals from `CD` (400) on are left out, since words such as `mix`
+/// and `mid` are made of their letters.
+pub fn parse_roman(text: &str) -> Option<usize> {
+    let upper = text.chars().all(|c| c.is_ascii_uppercase());
+    let lower = text.chars().all(|c| c.is_ascii_lowercase());
+    if !upper && !lower {
+        return None;
+    }
+    let mut total = 0;
+    let mut previous = 0;
+    for c in text.chars().rev() {
+        let value = match c.to_ascii_uppercase() {
+            'I' => 1,
+            'V' => 5,
+            'X' => 10,
+            'L' => 50,
+            'C' => 100,
+            'D' => 500,
+            'M' => 1000,
+            _ => return None,
+        };
+        if value < previous {
+            total -= value;
+        } else {
+            total += value;
+            previous = value;
+        }
+    }
+    // Only accept numerals written the usual way.
+    (total > 0 && total < MAX_ROMAN && to_roman(total).eq_ignore_ascii_case(text)).then_some(total)
+}
+
+fn to_roman(mut value: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut roman = String::new();
+    for &(step, numeral) in NUMERALS {
+        while value >= step {
+            roman.push_str(numeral);
+            value -= step;
+        }
+    }
+    roman
+}
+
+/// Parse numbers spelled out in words, such as `one hundred and twelve` or `двадцать первая`.
+fn parse_words(text: &str) -> Option<usize> {
+    let mut total: usize = 0;
+    let mut current: usize = 0;
+    let mut any = false;
+    for word in text
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|w| !w.is_empty())
+    {
+        let word = word.to_lowercase();
+        match word.as_str() {
+            "and" | "и" => continue,
+            "hundred" | "hundredth" => current = current.max(1).checked_mul(100)?,
+            "thousand" | "thousandth" | "тысяча" | "тысяч" | "тысячи" => {
+                total = current.max(1).checked_mul(1000)?.checked_add(total)?;
+                current = 0;
+            }
+            word => current = current.checked_add(word_value(word)?)?,
+        }
+        any = true;
+    }
+    if any {
+        total.checked_add(current)
+    } else {
+        None
+    }
+}
+
+fn word_value(word: &str) -> Option<usize> {
+    if let Some(&(_, value)) = ENGLISH.iter().chain(RUSSIAN).find(|(w, _)| *w == word) {
+        return Some(value);
+    }
+    let stem = RUSSIAN_ENDINGS
+        .iter()
+        .find_map(|ending| word.strip_suffix(ending))?;
+    RUSSIAN_ORDINALS
+        .iter()
+        .find(|(s, _)| *s == stem)
+        .map(|&(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_digits() {
+        assert_eq!(parse_number("17"), Some(17));
+        assert_eq!(parse_number(" 007 "), Some(7));
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn parses_roman_numerals() {
+        assert_eq!(parse_number("XIV"), Some(14));
+        assert_eq!(parse_number("xlii"), Some(42));
+        assert_eq!(parse_number("CCCXCIX"), Some(399));
+        assert_eq!(parse_number("I"), Some(1));
+    }
+
+    #[test]
+    fn rejects_malformed_roman_numerals() {
+        assert_eq!(parse_roman("IIII"), None);
+        assert_eq!(parse_roman("IC"), None);
+        assert_eq!(parse_roman("VX"), None);
+        assert_eq!(parse_roman("Xiv"), None);
+    }
+
+    #[test]
+    fn rejects_words_made_of_roman_letters() {
+        assert_eq!(parse_number("mix"), None);
+        assert_eq!(parse_number("Mix"), None);
+        assert_eq!(parse_number("mid"), None);
+        assert_eq!(parse_number("civil"), None);
+        assert_eq!(parse_number("MMXXIV"), None);
+    }
+
+    #[test]
+    fn parses_english_words() {
+        assert_eq!(parse_number("seventeen"), Some(17));
+        assert_eq!(parse_number("Twenty-First"), Some(21));
+        assert_eq!(parse_number("one hundred and twelve"), Some(112));
+        assert_eq!(parse_number("Chapter"), None);
+    }
+
+    #[test]
+    fn rejects_numbers_too_large_to_hold() {
+        assert_eq!(parse_number(&"hundred ".repeat(20)), None);
+        assert_eq!(parse_number("two thousand one hundred"), Some(2100));
+    }
+
+    #[test]
+    fn parses_russian_words() {
+        assert_eq!(parse_number("семнадцатая"), Some(17));
+        assert_eq!(parse_number("двадцать первая"), Some(21));
+        assert_eq!(parse_number("Глава"), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Where the files of a split go: a folder on disk, a ZIP archive, or memory for callers
+//! without a file system, such as the browser.
+
+use super::SplitError;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Destination for the files produced by a split.
+pub trait Sink {
+    /// Store a file under `name`, a relative path with `/` separators.
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), SplitError>;
+
+    /// Called once the last file is written.
+    fn finish(&mut self) -> Result<(), SplitError> {
+        Ok(())
+    }
+}
+
+/// Keeps files in memory as `(name, contents)` pairs.
+impl Sink for Vec<(String, Vec<u8>)> {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), SplitError> {
+        self.push((name.to_owned(), contents.to_vec()));
+        Ok(())
+    }
+}
+
+/// Writes files into a folder on disk.
+pub struct FolderSink {
+    folder: PathBuf,
+}
+
+impl FolderSink {
+    /// Create `folder` if it does not exist yet.
+    pub fn new(folder: impl Into<PathBuf>) -> Result<Self, SplitError> {
+        let folder = folder.into();
+        std::fs::create_dir_all(&folder)?;
+        Ok(Self { folder })
+    }
+}
+
+impl Sink for FolderSink {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), SplitError> {
+        let path = self.folder.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Packs files into a ZIP archive as they come, so that none is left loose on disk.
+pub struct ZipSink<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    /// Folder inside the archive the files go to, ending with `/`, or empty for the top.
+    root: String,
+}
+
+impl<W: Write + Seek> ZipSink<W> {
+    /// Start an archive in `writer` with files under the `root` folder, such as `chapters`, or
+    /// at the top if it is empty.
+    pub fn new(writer: W, root: &str) -> Self {
+        let root = root.trim_matches('/');
+        Self {
+            zip: ZipWriter::new(writer),
+            root: if root.is_empty() {
+                String::new()
+            } else {
+                format!("{root}/")
+            },
+        }
+    }
+}
+
+impl<W: Write + Seek> Sink for ZipSink<W> {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), SplitError> {
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        self.zip
+            .start_file(format!("{}{name}", self.root), options)?;
+        self.zip.write_all(contents)?;
+        Ok(())
+    }
+
+    /// Write the table of contents of the archive, without which it cannot be read, and flush
+    /// it so that a failed write is not mistaken for success.
+    fn finish(&mut self) -> Result<(), SplitError> {
+        self.zip.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// Where [`split_file`](super::split_file) puts chapters.
+#[derive(Clone, Debug)]
+pub enum Output {
+    /// A folder on disk, created if needed.
+    Folder(PathBuf),
+    /// A ZIP archive, replaced if it exists, with the files in the `root` folder inside it or
+    /// at the top if `root` is empty.
+    Zip { file: PathBuf, root: String },
+}
+
+impl Output {
+    /// Open a sink writing to this output.
+    pub fn open(&self) -> Result<Box<dyn Sink + Send>, SplitError> {
+        match self {
+            Output::Folder(folder) => Ok(Box::new(FolderSink::new(folder)?)),
+            Output::Zip { file, root } => {
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let writer = BufWriter::new(File::create(file)?);
+                Ok(Box::new(ZipSink::new(writer, root)))
+            }
+        }
+    }
+
+    /// The same kind of output for one book of a batch: the `folder` named after it, or an
+    /// archive named like it.
+    pub fn for_book(&self, folder: &Path) -> Output {
+        match self {
+            Output::Folder(_) => Output::Folder(folder.to_owned()),
+            Output::Zip { root, .. } => Output::Zip {
+                file: PathBuf::from(format!("{}.zip", folder.display())),
+                root: root.clone(),
+            },
+        }
+    }
+
+    /// Remove files written to this output, e.g. after a cancelled split. An archive goes as a
+    /// whole.
+    pub fn delete(&self, names: &[String]) -> Result<(), SplitError> {
+        match self {
+            Output::Folder(folder) => {
+                for name in names {
+                    std::fs::remove_file(folder.join(name))?;
+                }
+            }
+            Output::Zip { file, .. } => std::fs::remove_file(file)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    #[test]
+    fn writes_zip_entries_under_the_root() {
+        let file =
+            std::env::temp_dir().join(format!("book_splitter_sink_{}.zip", std::process::id()));
+        let output = Output::Zip {
+            file: file.clone(),
+            root: "/pg/chapters/".to_owned(),
+        };
+        let mut sink = output.open().unwrap();
+        sink.write("0001.txt", b"One.\n").unwrap();
+        sink.write("part/0002.txt", "Два.\n".as_bytes()).unwrap();
+        sink.finish().unwrap();
+        drop(sink);
+
+        let mut archive = ZipArchive::new(File::open(&file).unwrap()).unwrap();
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            entries.push((entry.name().to_owned(), contents));
+        }
+        assert_eq!(
+            entries,
+            [
+                ("pg/chapters/0001.txt".to_owned(), "One.\n".to_owned()),
+                ("pg/chapters/part/0002.txt".to_owned(), "Два.\n".to_owned()),
+            ]
+        );
+        output.delete(&[]).unwrap();
+        assert!(!file.exists());
+    }
+}
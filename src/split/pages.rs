@@ -0,0 +1,232 @@
+//! Finding the page numbers and running heads left in text taken from scans or PDFs.
+//!
+//! Page numbers are lines holding nothing but a number, perhaps with `Page` or dashes around
+//! it, that count up by about as many pages as lines go by. Running heads are short lines, such
+//! as the book title or the author, that come back every page or every other page, possibly
+//! with the page number in front or behind. Without page numbers there is no page length to
+//! go by, so nothing is taken for a running head.
+
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Fewest lines of a kind that make a pattern rather than chance.
+const MIN_REPEATS: usize = 5;
+
+/// Longest running head, in characters.
+const MAX_HEAD: usize = 80;
+
+/// Pages are at most this many lines long.
+const MAX_PAGE: usize = 100;
+
+/// Pages hold at most this many characters, which tells them from short chapters numbered
+/// alone on a line.
+const MAX_PAGE_CHARS: usize = 4000;
+
+/// What a removed line was taken for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageLineKind {
+    PageNumber,
+    RunningHead,
+}
+
+/// A line of page furniture found by [`find_page_lines`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageLine {
+    /// Zero-based index of the line in the book.
+    pub line: usize,
+    pub kind: PageLineKind,
+}
+
+/// Page numbers and running heads among the lines of `text`, in order.
+pub fn find_page_lines(text: &str) -> Vec<PageLine> {
+    let lines: Vec<&str> = text.lines().collect();
+    let numbers = page_numbers(&lines);
+    let page_length = page_length(&numbers);
+    let mut found: Vec<PageLine> = numbers
+        .iter()
+        .map(|&(line, _)| PageLine {
+            line,
+            kind: PageLineKind::PageNumber,
+        })
+        .collect();
+    found.extend(
+        running_heads(&lines, page_length)
+            .into_iter()
+            .map(|line| PageLine {
+                line,
+                kind: PageLineKind::RunningHead,
+            }),
+    );
+    found.sort_by_key(|page_line| page_line.line);
+    found.dedup_by_key(|page_line| page_line.line);
+    found
+}
+
+/// Lines that hold a page number, with the number, among the lines holding a number alone.
+fn page_numbers(lines: &[&str]) -> Vec<(usize, usize)> {
+    let pattern =
+        Regex::new(r"(?i)^\s*[-–—(\[]?\s*(?:page|p\.|стр\.?|с\.)?\s*(\d{1,4})\s*[-–—)\]]?\s*$")
+            .unwrap();
+    let candidates: Vec<(usize, usize)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| Some((i, pattern.captures(line)?[1].parse().ok()?)))
+        .collect();
+    let Some(length) = page_length(&candidates) else {
+        return Vec::new();
+    };
+
+    // A page number has a neighbour numbered about as many pages away as the lines between
+    // them fill; chapter numbers standing alone do not.
+    let fits = |(line, number): (usize, usize), (other_line, other_number): (usize, usize)| {
+        let pages = number.abs_diff(other_number);
+        let distance = line.abs_diff(other_line) as f64;
+        (1..=3).contains(&pages)
+            && distance >= pages as f64 * length * 0.5
+            && distance <= pages as f64 * length * 1.5
+    };
+    let numbers: Vec<(usize, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter(|&(i, &candidate)| {
+            let before = i.checked_sub(1).map(|i| candidates[i]);
+            let after = candidates.get(i + 1).copied();
+            before
+                .into_iter()
+                .chain(after)
+                .any(|other| fits(candidate, other))
+        })
+        .map(|(_, &candidate)| candidate)
+        .collect();
+    if numbers.len() < MIN_REPEATS {
+        return Vec::new();
+    }
+
+    let mut chars = vec![0];
+    for line in lines {
+        chars.push(chars[chars.len() - 1] + line.chars().count());
+    }
+    let mut page_chars: Vec<usize> = numbers
+        .windows(2)
+        .filter(|pair| pair[1].1 > pair[0].1)
+        .map(|pair| (chars[pair[1].0] - chars[pair[0].0]) / (pair[1].1 - pair[0].1))
+        .collect();
+    page_chars.sort_unstable();
+    if page_chars
+        .get(page_chars.len() / 2)
+        .is_none_or(|&median| median > MAX_PAGE_CHARS)
+    {
+        return Vec::new();
+    }
+    numbers
+}
+
+/// Typical number of lines per page going by `numbers` counting up, if they do.
+fn page_length(numbers: &[(usize, usize)]) -> Option<f64> {
+    let mut lengths: Vec<f64> = numbers
+        .windows(2)
+        .filter(|pair| pair[1].1 > pair[0].1 && pair[1].1 - pair[0].1 <= 3)
+        .map(|pair| (pair[1].0 - pair[0].0) as f64 / (pair[1].1 - pair[0].1) as f64)
+        .filter(|&length| length >= 2.0 && length <= MAX_PAGE as f64)
+        .collect();
+    if lengths.len() < MIN_REPEATS {
+        return None;
+    }
+    lengths.sort_by(f64::total_cmp);
+    Some(lengths[lengths.len() / 2])
+}
+
+/// Lines repeating, up to a page number around them, every page or every other page of
+/// `page_length` lines.
+fn running_heads(lines: &[&str], page_length: Option<f64>) -> Vec<usize> {
+    // Headers such as `Chapter 7` repeat too, just not at the length of a page.
+    let Some(page_length) = page_length else {
+        return Vec::new();
+    };
+    let mut repeats: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        let head = line
+            .trim()
+            .trim_matches(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .to_lowercase();
+        // A running head has letters in it.
+        if head.chars().any(char::is_alphabetic) && head.chars().count() <= MAX_HEAD {
+            repeats.entry(head).or_default().push(i);
+        }
+    }
+
+    let mut heads = Vec::new();
+    for occurrences in repeats.into_values() {
+        if occurrences.len() < MIN_REPEATS {
+            continue;
+        }
+        let mut gaps: Vec<usize> = occurrences.windows(2).map(|w| w[1] - w[0]).collect();
+        gaps.sort_unstable();
+        let typical = gaps[gaps.len() / 2] as f64;
+        let regular = gaps
+            .iter()
+            .filter(|&&gap| (gap as f64) >= typical * 0.7 && (gap as f64) <= typical * 1.3)
+            .count();
+        let pages = (typical / page_length).round();
+        let per_page = (1.0..=2.0).contains(&pages)
+            && (typical - pages * page_length).abs() <= page_length * 0.2;
+        if per_page && regular * 3 >= gaps.len() * 2 {
+            heads.extend(occurrences);
+        }
+    }
+    heads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pages` pages of `length` lines, each with `head` on top and its number at the bottom.
+    fn scanned(pages: usize, length: usize, head: &str) -> String {
+        let mut text = String::new();
+        for page in 1..=pages {
+            text.push_str(&format!("{head}\n"));
+            for line in 2..length {
+                text.push_str(&format!("Line {line} of page {page} goes here.\n"));
+            }
+            text.push_str(&format!("{page}\n"));
+        }
+        text
+    }
+
+    #[test]
+    fn finds_page_numbers_and_running_heads() {
+        let text = scanned(8, 30, "THE BOOK TITLE");
+        let found = find_page_lines(&text);
+        let count = |kind| found.iter().filter(|line| line.kind == kind).count();
+        assert_eq!(count(PageLineKind::PageNumber), 8);
+        assert_eq!(count(PageLineKind::RunningHead), 8);
+        assert_eq!(found[0].line, 0);
+        assert_eq!(found[1].line, 29);
+    }
+
+    #[test]
+    fn chapter_headers_are_not_running_heads() {
+        let mut text = String::new();
+        for chapter in 1..=20 {
+            text.push_str(&format!("Chapter {chapter}\n"));
+            for line in 0..30 {
+                text.push_str(&format!("Line {line} of the chapter.\n"));
+            }
+        }
+        assert!(find_page_lines(&text).is_empty());
+    }
+
+    #[test]
+    fn needs_a_page_length_for_running_heads() {
+        let mut text = String::new();
+        for _ in 0..10 {
+            text.push_str("Interlude\nA short scene.\n");
+            for line in 0..20 {
+                text.push_str(&format!("Words {line}.\n"));
+            }
+        }
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(running_heads(&lines, None).is_empty());
+    }
+}
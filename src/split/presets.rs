@@ -0,0 +1,120 @@
+//! Header regexes for common kinds of books.
+
+/// Version of [`CATALOG`], raised whenever presets are added or changed so that front ends can
+/// point out what is new.
+pub const CATALOG_VERSION: u32 = 1;
+
+/// A header regex that comes with the program.
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinPreset {
+    pub name: &'static str,
+    pub pattern: &'static str,
+    /// A header line the pattern matches, to show what it is for.
+    pub sample: &'static str,
+    /// [`CATALOG_VERSION`] the preset first appeared in.
+    pub since: u32,
+}
+
+/// A header regex saved by the user under a name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    pub pattern: String,
+}
+
+pub const CATALOG: &[BuiltinPreset] = &[
+    BuiltinPreset {
+        name: "Chapter N (English)",
+        pattern: r"^\s*(?i:chapter)\s+(?P<number>\d+|\p{L}+(?:-\p{L}+)*)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "Chapter 12: The Return",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Part N (English)",
+        pattern: r"^\s*(?i:part)\s+(?P<number>\d+|\p{L}+(?:-\p{L}+)*)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "PART ONE — Beginnings",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Глава N (Russian)",
+        pattern: r"^\s*(?i:глава)\s+(?P<number>\d+|\p{L}+(?:\s+\p{L}+)?)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "Глава пятая. Встреча",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Часть N (Russian)",
+        pattern: r"^\s*(?i:часть)\s+(?P<number>\d+|\p{L}+(?:\s+\p{L}+)?)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "Часть 2",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Kapitel N (German)",
+        pattern: r"^\s*(?i:kapitel)\s+(?P<number>\d+|\p{L}+)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "Kapitel 3 – Die Reise",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Chapitre N (French)",
+        pattern: r"^\s*(?i:chapitre)\s+(?P<number>\d+|\p{L}+)\b\s*[.:—–-]?\s*(?P<title>.*)$",
+        sample: "Chapitre premier",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Roman numeral alone",
+        pattern: r"^\s*(?P<number>[IVXLCDM]+)\.?\s*$",
+        sample: "XIV.",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Number alone",
+        pattern: r"^\s*(?P<number>\d+)\.?\s*$",
+        sample: "12",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Scene break * * *",
+        pattern: r"^\s*(\*\s*){3,}$",
+        sample: "* * *",
+        since: 1,
+    },
+    BuiltinPreset {
+        name: "Markdown # heading",
+        pattern: r"^#{1,3}\s+(?P<title>.+)$",
+        sample: "## The Return",
+        since: 1,
+    },
+];
+
+/// The built-in preset called `name`, ignoring case.
+pub fn builtin(name: &str) -> Option<&'static BuiltinPreset> {
+    CATALOG
+        .iter()
+        .find(|preset| preset.name.to_lowercase() == name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn presets_match_their_samples() {
+        for preset in CATALOG {
+            let regex = Regex::new(preset.pattern).unwrap();
+            assert!(regex.is_match(preset.sample), "{}", preset.name);
+            assert!(preset.since <= CATALOG_VERSION);
+        }
+    }
+
+    #[test]
+    fn finds_presets_by_name() {
+        let preset = builtin("chapter n (english)").unwrap();
+        assert_eq!(preset.name, "Chapter N (English)");
+        assert_eq!(
+            builtin("ГЛАВА N (RUSSIAN)").unwrap().name,
+            "Глава N (Russian)"
+        );
+        assert!(builtin("Chapter").is_none());
+    }
+}
@@ -0,0 +1,176 @@
+//! Joining hard-wrapped lines back into paragraphs.
+//!
+//! A book is taken to be wrapped when most of its lines end close to one width. A line then
+//! continues the paragraph before it when that paragraph filled its line up to the width, so
+//! short lines of poetry and dialogue stay apart, as do indented lines and list items.
+
+/// Share of the lines close to the wrap width needed to call a book wrapped.
+const WRAPPED_SHARE: f64 = 0.5;
+
+/// Widths books are wrapped at, in characters.
+const WRAP_WIDTHS: std::ops::RangeInclusive<usize> = 40..=100;
+
+/// How much shorter than the width a full line may be: the next word did not fit on it.
+const SLACK: usize = 20;
+
+/// Words that open hyphenated compounds, such as `well-known`, whose hyphen stays when a line
+/// ends with it.
+const COMPOUND_WORDS: &[&str] = &[
+    "anti", "cross", "half", "ill", "non", "quasi", "self", "semi", "well", "twenty", "thirty",
+    "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// The width `text` is hard-wrapped at, or `None` if it does not look wrapped.
+pub fn wrap_width(text: &str) -> Option<usize> {
+    let mut lengths: Vec<usize> = text
+        .lines()
+        .map(|line| line.trim_end().chars().count())
+        .filter(|&length| length > 0)
+        .collect();
+    if lengths.len() < 10 {
+        return None;
+    }
+    lengths.sort_unstable();
+    // A few overlong lines, such as URLs or tables, do not change the width.
+    let width = lengths[lengths.len() * 95 / 100];
+    if !WRAP_WIDTHS.contains(&width) {
+        return None;
+    }
+    let full = lengths
+        .iter()
+        .filter(|&&length| length + SLACK / 2 >= width && length <= width)
+        .count();
+    (full as f64 / lengths.len() as f64 >= WRAPPED_SHARE).then_some(width)
+}
+
+/// `lines` wrapped at `width` with the lines of every paragraph joined into one and words
+/// hyphenated across lines put back together. Blank lines stay.
+pub fn reflow(lines: &[&str], width: usize) -> Vec<String> {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut previous: Option<&str> = None;
+    for &line in lines {
+        let joins = previous.is_some_and(|previous| {
+            !line.trim().is_empty()
+                && !line.starts_with(char::is_whitespace)
+                && !starts_list_item(line)
+                && previous.trim_end().chars().count() + SLACK >= width
+        });
+        match paragraphs.last_mut() {
+            Some(paragraph) if joins => join(paragraph, line.trim_end()),
+            _ => paragraphs.push(line.trim_end().to_owned()),
+        }
+        previous = Some(line).filter(|line| !line.trim().is_empty());
+    }
+    paragraphs
+}
+
+/// Append `line` to `paragraph`, dropping the hyphen of a word broken across them unless it
+/// joins a compound.
+fn join(paragraph: &mut String, line: &str) {
+    if paragraph.ends_with('\u{ad}') {
+        paragraph.pop();
+    } else if let Some(before) = paragraph.strip_suffix('-') {
+        let word = before
+            .rsplit(|c: char| !c.is_alphabetic())
+            .next()
+            .unwrap_or_default();
+        let compound = COMPOUND_WORDS
+            .iter()
+            .any(|compound| compound.eq_ignore_ascii_case(word));
+        if !word.is_empty() && !compound && line.starts_with(char::is_lowercase) {
+            paragraph.pop();
+        }
+    } else if !paragraph.ends_with('—') {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(line);
+}
+
+/// The line opens a list item: a bullet, or a number or letter followed by `.` or `)`.
+fn starts_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with(['-', '*', '•', '–']) {
+        return line.chars().nth(1).is_some_and(char::is_whitespace);
+    }
+    let marker: String = line
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let rest = &line[marker.len()..];
+    let short_marker =
+        marker.chars().all(|c| c.is_ascii_digit()) && marker.len() <= 3 || marker.len() == 1;
+    !marker.is_empty()
+        && short_marker
+        && rest.starts_with(['.', ')'])
+        && rest[1..].starts_with(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAGRAPH: &str = "\
+It was the best of times, it was the worst of times, it was the age of
+wisdom, it was the age of foolishness, it was the epoch of belief, it
+was the epoch of incredulity, it was the season of Light, it was the
+season of Darkness, it was the spring of hope, it was the winter of
+despair.";
+
+    #[test]
+    fn finds_the_wrap_width() {
+        let text = format!("{PARAGRAPH}\n\n").repeat(3);
+        assert_eq!(wrap_width(&text), Some(70));
+        assert_eq!(wrap_width("Short.\n".repeat(20).as_str()), None);
+    }
+
+    #[test]
+    fn joins_the_lines_of_a_paragraph() {
+        let mut lines: Vec<&str> = PARAGRAPH.lines().collect();
+        lines.extend(["", "Next paragraph."]);
+        assert_eq!(
+            reflow(&lines, 70),
+            [
+                PARAGRAPH.replace('\n', " "),
+                String::new(),
+                "Next paragraph.".to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn puts_hyphenated_words_back_together() {
+        let lines = [
+            "A line long enough to be full, ending in the first half of an exam-",
+            "ple, and then another one long enough to be full, ending in well-",
+            "known words, and in a dash that ends a line long enough to be full—",
+            "like this.",
+        ];
+        assert_eq!(
+            reflow(&lines, 70),
+            [
+                "A line long enough to be full, ending in the first half of an example, \
+              and then another one long enough to be full, ending in well-known words, \
+              and in a dash that ends a line long enough to be full—like this."
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_verse_lists_and_indented_lines_apart() {
+        let verse = [
+            "Tyger Tyger, burning bright,",
+            "In the forests of the night;",
+        ];
+        assert_eq!(reflow(&verse, 70), verse);
+
+        let lines = [
+            "The list below follows a line long enough to be full, so it could join",
+            "1. the first item, which stays on its own line although the line above",
+            "- a bullet, which stays on its own line as well as the line above it is",
+            "    an indented line, which stays too",
+        ];
+        assert_eq!(reflow(&lines, 70), lines);
+        assert!(starts_list_item("a) an item"));
+        assert!(!starts_list_item("Mr. Darcy"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Cutting chapters into parts of about the same size.
+
+use super::Chapter;
+
+/// What chapter sizes are measured in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SizeUnit {
+    #[default]
+    Words,
+    Chars,
+    /// Bytes of the text in UTF-8.
+    Bytes,
+}
+
+/// Where chapters may be cut.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Boundary {
+    /// Before a paragraph: a line after a blank line or an indented line, or any line in text
+    /// without blank lines.
+    #[default]
+    Paragraph,
+    /// Before a paragraph or a sentence.
+    Sentence,
+}
+
+/// How big the parts of a chapter should be.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SizeLimit {
+    /// Size parts aim for.
+    pub target: usize,
+    pub unit: SizeUnit,
+    pub boundary: Boundary,
+    /// How much bigger or smaller than `target` a part may be, in percent, to end on a better
+    /// boundary. Chapters within the tolerance are not cut.
+    pub tolerance: usize,
+}
+
+impl Default for SizeLimit {
+    fn default() -> Self {
+        Self {
+            target: 5000,
+            unit: SizeUnit::Words,
+            boundary: Boundary::Paragraph,
+            tolerance: 10,
+        }
+    }
+}
+
+impl SizeLimit {
+    fn measure(&self, text: &str) -> usize {
+        match self.unit {
+            SizeUnit::Words => text.split_whitespace().count(),
+            SizeUnit::Chars => text.chars().count(),
+            SizeUnit::Bytes => text.len(),
+        }
+    }
+
+    /// Size of the `\n` ending every line.
+    fn line_end(&self) -> usize {
+        match self.unit {
+            SizeUnit::Words => 0,
+            SizeUnit::Chars | SizeUnit::Bytes => 1,
+        }
+    }
+}
+
+/// A place a chapter may be cut at: `lines[line][byte..]` starts the next part.
+#[derive(Clone, Copy)]
+struct Cut {
+    line: usize,
+    byte: usize,
+    /// Size of the chapter text before the cut.
+    before: usize,
+}
+
+/// Cut `chapter` into parts of about `limit.target`, or return it whole if it is no bigger than
+/// the tolerance allows. Parts keep the number of the chapter and get [`Chapter::part`] set;
+/// only the first one keeps the header.
+pub fn cut_by_size<'a>(chapter: Chapter<'a>, limit: &SizeLimit) -> Vec<Chapter<'a>> {
+    let (cuts, total) = candidate_cuts(&chapter.lines, limit);
+    let slack = limit.target * limit.tolerance / 100;
+    let (low, high) = (limit.target.saturating_sub(slack), limit.target + slack);
+    if total <= high || limit.target == 0 {
+        return vec![chapter];
+    }
+
+    let mut chosen: Vec<Cut> = Vec::new();
+    let mut part_start = 0;
+    for (i, cut) in cuts.iter().enumerate() {
+        let size = cut.before - part_start;
+        let next = cuts.get(i + 1).map_or(total, |next| next.before) - part_start;
+        if size == 0 || next <= limit.target {
+            continue;
+        }
+        // Cut here or at the next place, whichever lands closer to the target, unless this
+        // one is the last within the tolerance.
+        if size >= limit.target
+            || (next > high && size >= low)
+            || limit.target - size <= next - limit.target
+        {
+            chosen.push(*cut);
+            part_start = cut.before;
+        }
+    }
+    // A short tail goes to the part before it if that stays within the tolerance.
+    if total - part_start < low {
+        let previous_start = chosen.len().checked_sub(2).map_or(0, |i| chosen[i].before);
+        if total - previous_start <= high {
+            chosen.pop();
+        }
+    }
+    if chosen.is_empty() {
+        return vec![chapter];
+    }
+
+    let end = Cut {
+        line: chapter.lines.len(),
+        byte: 0,
+        before: total,
+    };
+    let mut start = Cut {
+        line: 0,
+        byte: 0,
+        before: 0,
+    };
+    let mut parts = Vec::with_capacity(chosen.len() + 1);
+    for (part, cut) in chosen.into_iter().chain([end]).enumerate() {
+        let mut lines = Vec::new();
+        for line in start.line..=cut.line.min(chapter.lines.len() - 1) {
+            let text = chapter.lines[line];
+            let from = if line == start.line { start.byte } else { 0 };
+            if line == cut.line {
+                if cut.byte > from {
+                    lines.push(text[from..cut.byte].trim_end());
+                }
+            } else {
+                lines.push(&text[from..]);
+            }
+        }
+        parts.push(Chapter {
+            number: chapter.number,
+            part: Some(part),
+            matter: chapter.matter,
+            level: chapter.level,
+            division: chapter.division,
+            parents: chapter.parents.clone(),
+            source_number: chapter.source_number,
+            header: chapter.header.filter(|_| part == 0),
+            title: chapter.title,
+            first_line: chapter.first_line + start.line,
+            lines,
+        });
+        start = cut;
+    }
+    parts
+}
+
+/// Places `lines` may be cut at, in order, and the size of all of them.
+fn candidate_cuts(lines: &[&str], limit: &SizeLimit) -> (Vec<Cut>, usize) {
+    let blank_separated = lines.iter().any(|line| line.trim().is_empty());
+    let mut cuts = Vec::new();
+    let mut size = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| lines[i]);
+        let starts_paragraph = !line.trim().is_empty()
+            && previous.is_some_and(|previous| {
+                !blank_separated
+                    || previous.trim().is_empty()
+                    || line.starts_with(char::is_whitespace)
+                    || limit.boundary == Boundary::Sentence
+                        && previous.trim_end().ends_with(is_sentence_end)
+            });
+        if starts_paragraph {
+            cuts.push(Cut {
+                line: i,
+                byte: 0,
+                before: size,
+            });
+        }
+
+        let mut start = 0;
+        if limit.boundary == Boundary::Sentence {
+            for byte in sentence_starts(line) {
+                size += limit.measure(&line[start..byte]);
+                cuts.push(Cut {
+                    line: i,
+                    byte,
+                    before: size,
+                });
+                start = byte;
+            }
+        }
+        size += limit.measure(&line[start..]) + limit.line_end();
+    }
+    (cuts, size)
+}
+
+fn is_sentence_end(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '…')
+}
+
+/// Byte offsets of sentences in `line` after its first, judged by end punctuation followed by
+/// a space and anything but a lowercase letter.
+fn sentence_starts(line: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if !is_sentence_end(c) {
+            continue;
+        }
+        // Closing quotes and brackets stay with the sentence.
+        while chars
+            .next_if(|&(_, c)| is_sentence_end(c) || is_closing(c))
+            .is_some()
+        {}
+        let mut spaced = false;
+        while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {
+            spaced = true;
+        }
+        if let Some(&(i, c)) = chars.peek() {
+            if spaced && !c.is_lowercase() {
+                starts.push(i);
+            }
+        }
+    }
+    starts
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, '"' | '\'' | ')' | ']' | '»' | '”' | '’')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::naming;
+    use crate::split::tests::chapter;
+
+    fn limit(target: usize, unit: SizeUnit, boundary: Boundary) -> SizeLimit {
+        SizeLimit {
+            target,
+            unit,
+            boundary,
+            tolerance: 10,
+        }
+    }
+
+    const PARAGRAPH: &str = "one two three four five six seven eight nine ten";
+
+    #[test]
+    fn labels_parts_with_letters() {
+        let mut lines = vec!["Chapter 2"];
+        for _ in 0..6 {
+            lines.extend(["", PARAGRAPH]);
+        }
+        // The header counts as two words, so the first part holds one paragraph too.
+        let parts = cut_by_size(
+            chapter(2, &lines),
+            &limit(12, SizeUnit::Words, Boundary::Paragraph),
+        );
+        let labels: Vec<String> = parts.iter().map(|part| part.number_label(4)).collect();
+        assert_eq!(
+            labels,
+            ["0002a", "0002b", "0002c", "0002d", "0002e", "0002f"]
+        );
+        assert_eq!(parts[0].header, Some("Chapter 2"));
+        assert!(parts[1..].iter().all(|part| part.header.is_none()));
+        assert_eq!(parts[1].first_line, 4);
+        assert!(parts.iter().all(|part| part.number == 2));
+    }
+
+    #[test]
+    fn labels_parts_past_z() {
+        assert_eq!(naming::number_label(2, Some(25), 4), "0002z");
+        assert_eq!(naming::number_label(2, Some(26), 4), "0002aa");
+        assert_eq!(naming::number_label(2, Some(27), 4), "0002ab");
+        assert_eq!(naming::number_label(2, Some(26 + 26 * 26), 4), "0002aaa");
+    }
+
+    #[test]
+    fn keeps_chapters_within_the_tolerance() {
+        let lines = ["Chapter 2", "", PARAGRAPH];
+        let limit = limit(11, SizeUnit::Words, Boundary::Paragraph);
+        assert_eq!(cut_by_size(chapter(2, &lines), &limit).len(), 1);
+    }
+
+    #[test]
+    fn cuts_between_sentences() {
+        let lines = [
+            "Chapter 2",
+            "",
+            "One two three. Four five six. Seven eight nine.",
+        ];
+        let sentences = cut_by_size(
+            chapter(2, &lines),
+            &limit(4, SizeUnit::Words, Boundary::Sentence),
+        );
+        let texts: Vec<String> = sentences.iter().map(Chapter::text).collect();
+        assert_eq!(
+            texts,
+            [
+                "Chapter 2\n\nOne two three.\n",
+                "Four five six.\n",
+                "Seven eight nine.\n"
+            ]
+        );
+
+        let paragraphs = cut_by_size(
+            chapter(2, &lines),
+            &limit(4, SizeUnit::Words, Boundary::Paragraph),
+        );
+        assert_eq!(paragraphs.len(), 2);
+    }
+
+    #[test]
+    fn measures_in_chars_and_bytes() {
+        let lines = ["Глава 2", "", "жжжж", "", "жжжж"];
+        // 9 characters with the line ends before the first paragraph, 15 before the second.
+        let chars = limit(10, SizeUnit::Chars, Boundary::Paragraph);
+        assert_eq!(cut_by_size(chapter(2, &lines), &chars).len(), 2);
+        // Cyrillic letters take two bytes, so the whole chapter is 33 bytes.
+        let bytes = limit(31, SizeUnit::Bytes, Boundary::Paragraph);
+        assert_eq!(cut_by_size(chapter(2, &lines), &bytes).len(), 1);
+        let bytes = limit(15, SizeUnit::Bytes, Boundary::Paragraph);
+        assert_eq!(cut_by_size(chapter(2, &lines), &bytes).len(), 2);
+    }
+
+    #[test]
+    fn keeps_paragraphs_bigger_than_the_limit_whole() {
+        let lines = ["Chapter 2", "", PARAGRAPH, "", PARAGRAPH];
+        let parts = cut_by_size(
+            chapter(2, &lines),
+            &limit(3, SizeUnit::Words, Boundary::Paragraph),
+        );
+        let texts: Vec<String> = parts.iter().map(Chapter::text).collect();
+        assert_eq!(
+            texts,
+            [
+                "Chapter 2\n\n".to_owned(),
+                format!("{PARAGRAPH}\n\n"),
+                format!("{PARAGRAPH}\n")
+            ]
+        );
+        assert!(parts.iter().all(|part| !part.lines.is_empty()));
+    }
+}
@@ -0,0 +1,152 @@
+//! Helpers shared by the XML based book formats.
+
+use super::SplitError;
+
+/// Escape text for use in XML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Accumulates text a line per block element, collapsing whitespace like a browser.
+#[derive(Default)]
+pub struct TextWriter {
+    text: String,
+    line: String,
+    /// Number of lines written so far, which is also the index of the line being built.
+    lines: usize,
+}
+
+impl TextWriter {
+    /// Index of the line being built, which is also the number of lines written so far.
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn into_text(mut self) -> String {
+        self.break_line();
+        self.text
+    }
+
+    /// Append `text` to the current line, collapsing whitespace.
+    pub fn write_text(&mut self, text: &str) {
+        for (i, word) in text.split(char::is_whitespace).enumerate() {
+            if i > 0 && !self.line.is_empty() && !self.line.ends_with(' ') {
+                self.line.push(' ');
+            }
+            self.line.push_str(word);
+        }
+    }
+
+    /// End the current line, dropping it if blank.
+    pub fn break_line(&mut self) {
+        let line = self.line.trim();
+        if !line.is_empty() {
+            self.text.push_str(line);
+            self.text.push('\n');
+            self.lines += 1;
+        }
+        self.line.clear();
+    }
+}
+
+/// Text content of an element with whitespace collapsed.
+pub fn element_text(node: roxmltree::Node<'_, '_>) -> String {
+    let text: Vec<&str> = node
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect();
+    text.concat()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Join items into a tree by their depth. An item deeper than the one before it opens a list of
+/// children inside it with `open_children`, and every item ends with `close_item` after its
+/// children.
+pub fn nest(
+    items: impl IntoIterator<Item = (usize, String)>,
+    open_children: &str,
+    close_children: &str,
+    close_item: &str,
+) -> String {
+    let mut tree = String::new();
+    let mut current: Option<usize> = None;
+    for (depth, item) in items {
+        let depth = match current {
+            None => 0,
+            Some(current) if depth > current => {
+                tree.push_str(open_children);
+                current + 1
+            }
+            Some(current) => {
+                tree.push_str(close_item);
+                for _ in depth..current {
+                    tree.push_str(close_children);
+                    tree.push_str(close_item);
+                }
+                depth
+            }
+        };
+        tree.push_str(&item);
+        current = Some(depth);
+    }
+    if let Some(current) = current {
+        tree.push_str(close_item);
+        for _ in 0..current {
+            tree.push_str(close_children);
+            tree.push_str(close_item);
+        }
+    }
+    tree
+}
+
+pub fn parse_xml(text: &str) -> Result<roxmltree::Document<'_>, SplitError> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    Ok(roxmltree::Document::parse_with_options(text, options)?)
+}
+
+/// Current UTC time as `CCYY-MM-DDThh:mm:ssZ`.
+pub fn utc_now() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since 1970-01-01, see
+    // http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
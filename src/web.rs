@@ -0,0 +1,76 @@
+//! Browser stand-ins for file dialogs and the file system: books are picked through a file input
+//! and results saved as downloads.
+
+use crossbeam_channel::Sender;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, Url};
+
+/// A file picked by the user: its name and contents.
+pub type Upload = (String, Vec<u8>);
+
+/// How long a download's object URL is kept, in milliseconds.
+const REVOKE_AFTER_MS: i32 = 60_000;
+
+/// Ask the user for a file with one of the `accept`ed extensions, such as `.txt,.epub`. The
+/// file is read in the background and sent to `sender`, repainting `ctx` once it is in.
+pub fn pick_file(accept: &str, sender: Sender<Upload>, ctx: egui::Context) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    let Ok(input) = document
+        .create_element("input")
+        .map(JsCast::unchecked_into::<HtmlInputElement>)
+    else {
+        return;
+    };
+    input.set_type("file");
+    input.set_accept(accept);
+    let picked = input.clone();
+    let on_change = Closure::once(move || {
+        let Some(file) = picked.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            match JsFuture::from(file.array_buffer()).await {
+                Ok(buffer) => {
+                    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                    sender.send((file.name(), bytes)).ok();
+                    ctx.request_repaint();
+                }
+                Err(e) => log::error!("cannot read {}: {e:?}", file.name()),
+            }
+        });
+    });
+    input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+    // The input outlives this call, and so must its handler.
+    on_change.forget();
+    input.click();
+}
+
+/// Offer `contents` to the user as a download named `name`.
+pub fn download(name: &str, contents: &[u8], mime_type: &str) -> std::io::Result<()> {
+    offer_download(name, contents, mime_type).map_err(|e| std::io::Error::other(format!("{e:?}")))
+}
+
+fn offer_download(name: &str, contents: &[u8], mime_type: &str) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let document = window.document().ok_or("no document")?;
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor: HtmlAnchorElement = document.create_element("a")?.unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    // The download starts after this returns, so revoking the URL right away could cancel it.
+    let revoke = Closure::once_into_js(move || Url::revoke_object_url(&url));
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(
+        revoke.unchecked_ref(),
+        REVOKE_AFTER_MS,
+    )?;
+    Ok(())
+}